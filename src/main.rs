@@ -3,20 +3,25 @@ extern crate piston_window;
 extern crate swarm_it;
 extern crate chrono;
 extern crate rand;
+extern crate rand_chacha;
 extern crate time;
 
 use chrono::prelude::*;
 use piston_window::*;
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaChaRng;
 use std::char;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::fs::File;
-use std::io::{BufRead, BufReader, Read, Result};
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read, Result, Write};
 use std::ops::Add;
+use std::sync::OnceLock;
+use std::time::Instant;
 use swarm_it::{Carrier, Payload, Slot, SlotKind};
 use time::Duration;
 
 const SCREEN_SIZE_NATIVE: [u32; 2] = [1920, 1080];
+const WINDOW_SIZE_DEFAULT: [u32; 2] = [960, 540];
 const TILE_WIDTH: u32 = 44;
 const TILE_HEIGHT: u32 = 54;
 const TILE_SPACING: u32 = (50 - TILE_WIDTH) / 2;
@@ -27,20 +32,105 @@ const BOARD_LEFT_MARGIN: u32 =
 const BOARD_TOP_MARGIN: u32 =
     (SCREEN_SIZE_NATIVE[1] - (TILE_HEIGHT + TILE_SPACING) * TILES_PER_COLUMN) / 2;
 const TILE_DELIMITER: char = '^';
+const LAYOUT_DIRECTIVE: char = '@';
+const DEFAULT_BACKGROUND: &str = "images/backgrounds/darlington.jpg";
+const BACKGROUND_COLOR: [f32; 4] = [0.05, 0.05, 0.1, 1.0];
+const MISSING_TEXTURE_COLOR: [f32; 4] = [1.0, 0.0, 1.0, 0.85];
+const LAYOUT_ID: u32 = 2;
+const LAYOUTS_DIR: &str = "layouts/";
+const STATIONS_FILE: &str = "stations/uk.txt";
+const HOT_RELOAD_INTERVAL: u64 = 1; // Seconds
+const TILE_TINT: [f32; 4] = [1.0, 1.0, 1.0, 0.85];
+const TILE_COLOR_AMBER: [f32; 4] = [1.0, 0.75, 0.2, 1.0];
+const TILE_COLOR_RED: [f32; 4] = [1.0, 0.3, 0.25, 1.0];
+const TILE_COLOR_GREEN: [f32; 4] = [0.4, 1.0, 0.45, 1.0];
+const IMAGES_DIR: &str = "images/";
+const THEMES_DIR: &str = "themes/";
+const THEME_FILE: &str = "theme.txt";
+const NO_BACKGROUND: &str = "none";
+const BACKGROUND_INTERVAL: u64 = 30; // Seconds
+const BACKGROUND_FADE_DURATION: u64 = 3; // Seconds
 const EMPTY_PAYLOAD: char = ' ';
-const CARRIER_ANIM_SPEED: u32 = 8;
+const CARRIER_ANIM_FRAME_RATE: f64 = 7.5; // Frames per second when hovering
+const CARRIER_MAX_TILT: f64 = std::f64::consts::PI / 6.0;
+const CARRIER_TILT_RATE: f64 = 3.0; // Radians per second
+const CARRIER_TURN_RATE: f64 = 4.0; // Full turnarounds take half a second
+const CARRIER_ANIM_SPEED_FACTOR: f64 = 0.5; // Extra frames per second per pixel travelled in an update
+const FLAP_GLYPHS: &str = " ABCDEFGHIJKLMNOPQRSTUVWXYZĄĆĘŁŃÓŚŹŻ0123456789:.-()&'";
+const FLAP_STEP_DURATION: u64 = 4; // Ticks
 const CARRIER_ICON_X_OFFSET: f64 = 0.0;
 const CARRIER_ICON_Y_OFFSET: f64 = -50.0;
-const MAX_STATION_NAME_LENGTH: usize = 31;
+const MAX_STATION_NAME_LENGTH: usize = 26;
+const MAX_ORIGIN_NAME_LENGTH: usize = 16;
+const TITLE_ROW: u32 = 0;
+const HEADER_ROW: u32 = 1;
+const CLOCK_COLUMN: u32 = TILES_PER_ROW - 8;
+const CLOCK_FORMAT: &str = "%H:%M:%S";
+const SCHEDULE_HORIZON: i64 = 24; // Hours
+const PAGE_INTERVAL: u64 = 20; // Seconds
+                               // Page indicator must be placed in one of the header rows
+const PAGE_INDICATOR_ROW: u32 = TITLE_ROW;
+const PAGE_INDICATOR_COLUMN: u32 = 13;
+const CALLING_POINTS_PREFIX: &str = "CALLING AT: ";
+const CALLING_POINTS_SEPARATOR: &str = " - ";
+const CALLING_POINTS_GAP: &str = "     ";
+const CALLING_POINTS_MINIMUM: usize = 2;
+const CALLING_POINTS_MAXIMUM: usize = 8;
+const CALLING_POINTS_SCROLL_INTERVAL: u64 = 1; // Seconds
+const DEPARTURE_TIME_COLUMN: u32 = TILES_PER_ROW - 5;
+const DEPARTURE_PLATFORM_COLUMN: u32 = TILES_PER_ROW - 9;
+const ARRIVAL_PLATFORM_COLUMN: u32 = TILES_PER_ROW - 19;
+const PLATFORM_MAXIMUM: u32 = 12;
+const SCHEDULED_ARRIVAL_COLUMN: u32 = TILES_PER_ROW - 16;
+const EXPECTED_ARRIVAL_COLUMN: u32 = TILES_PER_ROW - 10;
+const ARRIVAL_DWELL_TIME: i64 = 1; // Minutes an arrived train stays on the board
 const TIME_DIFFERENCE_MINIMUM: i64 = 13; // Minutes
 const TIME_DIFFERENCE_MAXMIMUM: i64 = 90; // Minutes
+const ARRIVAL_DELAY_PROBABILITY: f64 = 0.25;
+const ARRIVAL_DELAY_MAXIMUM: i64 = 25; // Minutes
+const ARRIVAL_DELAY_UNKNOWN: i64 = 15; // Minutes, longer delays are shown as DELAYED
+const DELAYED_TEXT: &str = "DELAYED";
 const MAX_CARRIERS: u8 = 100;
+const TIME_SCALE_MIN: f64 = 0.25;
+const TIME_SCALE_MAX: f64 = 8.0;
+const METRICS_CSV_HEADER: &str =
+    "transition,causes,ticks,distance,pickups,drops,idle_ratio,interrupted";
+const CARRIER_TYPES_FILE: &str = "carriers/types.txt";
+const CARRIER_SPRITE_MAX_FRAMES: usize = 32;
+const MIN_CARRIERS: u8 = 1;
+const AUTO_SWARM_MIN_CARRIERS: u8 = 2;
+const AUTO_SWARM_MAX_CARRIERS: u8 = 40;
+const AUTO_SWARM_SLOTS_PER_CARRIER: usize = 6;
+const AUTO_SWARM_SPAWN_INTERVAL: u64 = 10; // Ticks
+const AUTO_SWARM_RETIRE_INTERVAL: u64 = 60; // Ticks
+const RETIRED_CARRIER_SPEED_FACTOR: f64 = 0.5; // Of the max speed of its type
 const CARRIER_ACCELERATION: f64 = 0.16;
 const CARRIER_MAX_SPEED: f64 = 24.0;
+const UPDATES_PER_SECOND: u64 = 60;
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+const CANCELLED_TEXT: &str = "CANCELLED";
+const CANCELLED_DISPLAY_TIME: u64 = 8; // Seconds
+const EXIT_MARGIN: f64 = 75.0; // How far off screen the pits and spawners are placed
+const DEFAULT_EXITS: [&str; 2] = ["left", "right"];
+const DEBUG_TARGET_COLOR: [f32; 4] = [1.0, 1.0, 0.0, 0.8];
+const DEBUG_VELOCITY_COLOR: [f32; 4] = [0.0, 1.0, 1.0, 0.9];
+const DEBUG_PENDING_COLOR: [f32; 4] = [1.0, 0.0, 0.0, 0.9];
+const DEBUG_PIT_COLOR: [f32; 4] = [1.0, 0.0, 1.0, 0.9];
+const DEBUG_SPAWNER_COLOR: [f32; 4] = [0.0, 1.0, 0.0, 0.9];
+const DEBUG_VELOCITY_SCALE: f64 = 10.0;
+const DEBUG_MARKER_SIZE: f64 = 20.0;
+const DEBUG_TEXT_SCALE: f64 = 0.4;
+const TRAIL_LENGTH: usize = 12; // Updates
+const TRAIL_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.35];
+const TRAIL_WIDTH: f64 = 3.0;
+const PARTICLES_PER_DROP: usize = 10;
+const MAX_PARTICLES: usize = 600;
+const PARTICLE_LIFETIME: u32 = 30; // Updates
+const PARTICLE_SPEED: f64 = 3.0;
+const PARTICLE_SIZE: f64 = 4.0;
 
 #[derive(Copy, Clone, Eq, PartialEq, Hash)]
 enum TextureId {
-    Background,
     Carrier01,
     Carrier02,
     Carrier03,
@@ -49,6 +139,8 @@ enum TextureId {
     Carrier06,
     Carrier07,
     Carrier08,
+    // Frame of the sprite set of a carrier type loaded from CARRIER_TYPES_FILE
+    CarrierSprite(usize, usize),
     Test,
     TileBlank,
     TileQ,
@@ -165,14 +257,393 @@ impl TextureId {
     }
 }
 
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum BoardKind {
+    Departures,
+    Arrivals,
+}
+
+// What the board shows and the rows reserved for the title with the clock and the column headers
+#[derive(Copy, Clone, Eq, PartialEq)]
+struct BoardMode {
+    kind: BoardKind,
+    title_row: u32,
+    header_row: u32,
+    page_indicator: [u32; 2],
+}
+
+impl BoardMode {
+    fn from_args() -> BoardMode {
+        let kind = if arg_flag("--arrivals") {
+            BoardKind::Arrivals
+        } else {
+            BoardKind::Departures
+        };
+        let mut mode = BoardMode::new(kind);
+        let title_row = arg_number("--title-row", TITLE_ROW);
+        let header_row = arg_number("--header-row", HEADER_ROW);
+        if title_row == header_row || title_row.max(header_row) >= TILES_PER_COLUMN {
+            println!("Invalid title or header row, using the default ones");
+        } else {
+            mode.title_row = title_row;
+            mode.header_row = header_row;
+        }
+
+        // Indicator follows the title row when it is not in any of the header rows
+        let row = arg_number("--page-indicator-row", PAGE_INDICATOR_ROW);
+        mode.page_indicator = [
+            if row == mode.header_row {
+                row
+            } else {
+                mode.title_row
+            },
+            arg_number("--page-indicator-column", PAGE_INDICATOR_COLUMN),
+        ];
+        mode
+    }
+
+    fn new(kind: BoardKind) -> BoardMode {
+        BoardMode {
+            kind,
+            title_row: TITLE_ROW,
+            header_row: HEADER_ROW,
+            page_indicator: [PAGE_INDICATOR_ROW, PAGE_INDICATOR_COLUMN],
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self.kind {
+            BoardKind::Departures => "DEPARTURES",
+            BoardKind::Arrivals => "ARRIVALS",
+        }
+    }
+
+    fn header(self) -> String {
+        match self.kind {
+            BoardKind::Departures => format!("{:<27}{:<4}{}", "DESTINATION", "PLAT", "TIME"),
+            BoardKind::Arrivals => {
+                format!("{:<17}{:<3}{:<6}{}", "FROM", "PL", "SCHED", "EXPECTED")
+            }
+        }
+    }
+
+    fn max_station_name_length(self) -> usize {
+        match self.kind {
+            BoardKind::Departures => MAX_STATION_NAME_LENGTH,
+            BoardKind::Arrivals => MAX_ORIGIN_NAME_LENGTH,
+        }
+    }
+
+    fn platform_column(self) -> u32 {
+        match self.kind {
+            BoardKind::Departures => DEPARTURE_PLATFORM_COLUMN,
+            BoardKind::Arrivals => ARRIVAL_PLATFORM_COLUMN,
+        }
+    }
+
+    // Status such as CANCELLED replaces the platform and the departure time,
+    // or the expected arrival time
+    fn status_column(self) -> u32 {
+        match self.kind {
+            BoardKind::Departures => TILES_PER_ROW - CANCELLED_TEXT.len() as u32,
+            BoardKind::Arrivals => EXPECTED_ARRIVAL_COLUMN,
+        }
+    }
+
+    fn has_calling_points(self) -> bool {
+        self.kind == BoardKind::Departures
+    }
+
+    // All rows except the header ones, the top train always goes first
+    fn body_rows(self) -> Vec<u32> {
+        (0..TILES_PER_COLUMN)
+            .filter(|row| *row != self.title_row && *row != self.header_row)
+            .collect()
+    }
+
+    // The calling points are displayed right below the top train
+    fn calling_points_row(self) -> u32 {
+        self.body_rows()[1]
+    }
+
+    // Board rows occupied by trains, in the order they leave the board
+    fn train_rows(self) -> Vec<u32> {
+        let mut rows = self.body_rows();
+        if self.has_calling_points() {
+            rows.remove(1);
+        }
+        rows
+    }
+
+    // Rows whose changes make up the board transitions, the ticking clock is not one of them
+    fn transition_rows(self) -> Vec<u32> {
+        (0..TILES_PER_COLUMN)
+            .filter(|row| *row != self.title_row)
+            .collect()
+    }
+
+    // Rows the auto-swarm sizes the swarm for, the scrolling calling points change all the time
+    // just like the clock does
+    fn demand_rows(self) -> Vec<u32> {
+        let mut rows = self.transition_rows();
+        if self.has_calling_points() {
+            rows.retain(|row| *row != self.calling_points_row());
+        }
+        rows
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum DisplayMode {
+    Swarm,
+    SplitFlap,
+}
+
+impl DisplayMode {
+    fn from_args() -> DisplayMode {
+        if arg_flag("--split-flap") {
+            DisplayMode::SplitFlap
+        } else {
+            DisplayMode::Swarm
+        }
+    }
+
+    fn toggled(self) -> DisplayMode {
+        match self {
+            DisplayMode::Swarm => DisplayMode::SplitFlap,
+            DisplayMode::SplitFlap => DisplayMode::Swarm,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum ScaleMode {
+    Letterbox,
+    Stretch,
+    Crop,
+}
+
+impl ScaleMode {
+    fn from_args() -> ScaleMode {
+        match arg_value("--scale").as_deref() {
+            Some("stretch") => ScaleMode::Stretch,
+            Some("crop") => ScaleMode::Crop,
+            _ => ScaleMode::Letterbox,
+        }
+    }
+}
+
+// Maps the logical board coordinates (SCREEN_SIZE_NATIVE) onto the actual window
+#[derive(Copy, Clone)]
+struct ViewTransform {
+    window_size: [f64; 2],
+    offset: [f64; 2],
+    scale: [f64; 2],
+}
+
+impl ViewTransform {
+    fn new(window_size: [f64; 2], scale_mode: ScaleMode) -> ViewTransform {
+        let native_size = [
+            f64::from(SCREEN_SIZE_NATIVE[0]),
+            f64::from(SCREEN_SIZE_NATIVE[1]),
+        ];
+        let scale_x = window_size[0] / native_size[0];
+        let scale_y = window_size[1] / native_size[1];
+        let scale = match scale_mode {
+            ScaleMode::Letterbox => [scale_x.min(scale_y); 2],
+            ScaleMode::Stretch => [scale_x, scale_y],
+            ScaleMode::Crop => [scale_x.max(scale_y); 2],
+        };
+        ViewTransform {
+            window_size,
+            offset: [
+                (window_size[0] - native_size[0] * scale[0]) / 2.0,
+                (window_size[1] - native_size[1] * scale[1]) / 2.0,
+            ],
+            scale,
+        }
+    }
+
+    fn apply(&self, ctx: Context) -> Context {
+        ctx.trans(self.offset[0], self.offset[1])
+            .scale(self.scale[0], self.scale[1])
+    }
+
+    // Bars covering whatever is painted outside of the board, e.g. the carriers near the pits
+    fn letterbox_bars(&self) -> Vec<[f64; 4]> {
+        let [width, height] = self.window_size;
+        let [x, y] = self.offset;
+        vec![
+            [0.0, 0.0, width, y],
+            [0.0, height - y, width, y],
+            [0.0, 0.0, x, height],
+            [width - x, 0.0, x, height],
+        ]
+        .into_iter()
+        .filter(|bar| bar[2] > 0.0 && bar[3] > 0.0)
+        .collect()
+    }
+}
+
+// Options changing the course of the simulation, and whether they take a value.
+// They are written into the recording and restored by the replay
+const SIMULATION_OPTIONS: [(&str, bool); 14] = [
+    ("--arrivals", false),
+    ("--title-row", true),
+    ("--header-row", true),
+    ("--page-indicator-row", true),
+    ("--page-indicator-column", true),
+    ("--split-flap", false),
+    ("--theme", true),
+    ("--auto-swarm", false),
+    ("--min-carriers", true),
+    ("--max-carriers", true),
+    ("--spawn-interval", true),
+    ("--retire-interval", true),
+    ("--cancelled-time", true),
+    ("--page-interval", true),
+];
+
+// Simulation options of the replayed recording, they replace those given on the command line
+static REPLAYED_OPTIONS: OnceLock<Vec<String>> = OnceLock::new();
+
+fn args() -> Vec<String> {
+    let mut args: Vec<String> = std::env::args().collect();
+    if let Some(replayed) = REPLAYED_OPTIONS.get() {
+        let given = std::mem::take(&mut args);
+        let mut given = given.into_iter();
+        while let Some(arg) = given.next() {
+            match SIMULATION_OPTIONS.iter().find(|(name, _)| *name == arg) {
+                Some((_, true)) => {
+                    given.next();
+                }
+                Some((_, false)) => {}
+                None => args.push(arg),
+            }
+        }
+        args.extend(replayed.iter().cloned());
+    }
+    args
+}
+
+// Simulation options in effect, one option with its value per entry
+fn simulation_options() -> Vec<String> {
+    let args = args();
+    SIMULATION_OPTIONS
+        .iter()
+        .filter_map(|(name, takes_value)| {
+            if *takes_value {
+                arg_value(name).map(|value| format!("{} {}", name, value))
+            } else {
+                args.iter().find(|arg| arg == name).cloned()
+            }
+        })
+        .collect()
+}
+
+fn arg_flag(name: &str) -> bool {
+    args().iter().any(|arg| arg == name)
+}
+
+fn arg_value(name: &str) -> Option<String> {
+    args()
+        .windows(2)
+        .find(|pair| pair[0] == name)
+        .map(|pair| pair[1].clone())
+}
+
+// Values that cannot be parsed are ignored in favour of the default
+fn arg_number<T: std::str::FromStr>(name: &str, default: T) -> T {
+    arg_value(name)
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+// Region of the board or status of the train the tile belongs to, themes pick the colours
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+enum TileColor {
+    Default,
+    Title,
+    Header,
+    Clock,
+    Time,
+    OnTime,
+    Late,
+    Delayed,
+    Cancelled,
+    Platform,
+    CallingPoints,
+}
+
+impl TileColor {
+    fn from_name(name: &str) -> Option<TileColor> {
+        match name {
+            "text" => Some(TileColor::Default),
+            "title" => Some(TileColor::Title),
+            "header" => Some(TileColor::Header),
+            "clock" => Some(TileColor::Clock),
+            "time" => Some(TileColor::Time),
+            "on_time" => Some(TileColor::OnTime),
+            "late" => Some(TileColor::Late),
+            "delayed" => Some(TileColor::Delayed),
+            "cancelled" => Some(TileColor::Cancelled),
+            "platform" => Some(TileColor::Platform),
+            "calling_points" => Some(TileColor::CallingPoints),
+            _ => None,
+        }
+    }
+
+    fn default_rgba(self) -> [f32; 4] {
+        match self {
+            TileColor::Clock | TileColor::Time => TILE_COLOR_AMBER,
+            TileColor::Late | TileColor::Delayed | TileColor::Cancelled => TILE_COLOR_RED,
+            TileColor::OnTime | TileColor::Platform => TILE_COLOR_GREEN,
+            _ => [1.0; 4],
+        }
+    }
+
+    // Colours are dimmed along with the rest of the board by the theme tint
+    fn rgba(self, theme: &Theme) -> [f32; 4] {
+        let color = theme
+            .colors
+            .get(&self)
+            .copied()
+            .unwrap_or_else(|| self.default_rgba());
+        [
+            color[0] * theme.tint[0],
+            color[1] * theme.tint[1],
+            color[2] * theme.tint[2],
+            color[3] * theme.tint[3],
+        ]
+    }
+}
+
+// Cargo carried by the swarm, a tile along with the colour it is painted with
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+struct Glyph {
+    texture: TextureId,
+    color: TileColor,
+}
+
 struct TextureDef {
     id: TextureId,
     path: &'static str,
 }
 
-type MyGameType = swarm_it::Swarm<TextureId>;
+type MyGameType = swarm_it::Swarm<Glyph>;
+
+const CARRIER_FRAMES: [TextureId; 8] = [
+    TextureId::Carrier01,
+    TextureId::Carrier02,
+    TextureId::Carrier03,
+    TextureId::Carrier04,
+    TextureId::Carrier05,
+    TextureId::Carrier06,
+    TextureId::Carrier07,
+    TextureId::Carrier08,
+];
 
-const TEXTURE_REPOSITORY: [TextureDef; 63] = [
+const TEXTURE_REPOSITORY: [TextureDef; 62] = [
     TextureDef {
         id: TextureId::Test,
         path: "images/test_image.png",
@@ -209,10 +680,6 @@ const TEXTURE_REPOSITORY: [TextureDef; 63] = [
         id: TextureId::Carrier08,
         path: "images/carrier/frame-8.png",
     },
-    TextureDef {
-        id: TextureId::Background,
-        path: "images/backgrounds/darlington.jpg",
-    },
     TextureDef {
         id: TextureId::TileBlank,
         path: "images/tiles/tile_blank.png",
@@ -427,14 +894,274 @@ const TEXTURE_REPOSITORY: [TextureDef; 63] = [
     },
 ];
 
-fn load_textures(depot: &mut HashMap<TextureId, G2dTexture>, context: &mut G2dTextureContext) {
+// Textures that could not be loaded, along with the reason
+#[derive(Debug)]
+struct TextureLoadError {
+    failures: Vec<(String, String)>,
+}
+
+impl std::fmt::Display for TextureLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Unable to load {} texture(s):", self.failures.len())?;
+        for (path, reason) in &self.failures {
+            write!(f, "\n  '{}': {}", path, reason)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for TextureLoadError {}
+
+// Loads all the textures it can, the missing ones are painted as placeholders
+fn load_textures(
+    depot: &mut HashMap<TextureId, G2dTexture>,
+    context: &mut G2dTextureContext,
+    theme: &Theme,
+) -> std::result::Result<(), TextureLoadError> {
+    let mut failures = Vec::new();
     TEXTURE_REPOSITORY.iter().for_each(|x| {
-        println!("{:?}", x.path);
-        depot.insert(
-            x.id,
-            Texture::from_path(context, x.path, Flip::None, &TextureSettings::new()).unwrap(),
-        );
+        let path = theme.resolve(x.path);
+        println!("{:?}", path);
+        match Texture::from_path(context, &path, Flip::None, &TextureSettings::new()) {
+            Ok(texture) => {
+                depot.insert(x.id, texture);
+            }
+            Err(e) => failures.push((path, e.to_string())),
+        }
     });
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(TextureLoadError { failures })
+    }
+}
+
+fn draw_texture(texture: Option<&G2dTexture>, color: [f32; 4], context: &Context, g: &mut G2d) {
+    match texture {
+        Some(texture) => {
+            Image::new_color(color).draw(texture, &context.draw_state, context.transform, g)
+        }
+        None => draw_missing_texture(context, g),
+    }
+}
+
+// Crossed out tile, clearly visible on the board
+fn draw_missing_texture(context: &Context, g: &mut G2d) {
+    let width = f64::from(TILE_WIDTH);
+    let height = f64::from(TILE_HEIGHT);
+    rectangle(
+        MISSING_TEXTURE_COLOR,
+        [0.0, 0.0, width, height],
+        context.transform,
+        g,
+    );
+    line(
+        [0.0, 0.0, 0.0, 1.0],
+        2.0,
+        [0.0, 0.0, width, height],
+        context.transform,
+        g,
+    );
+    line(
+        [0.0, 0.0, 0.0, 1.0],
+        2.0,
+        [width, 0.0, 0.0, height],
+        context.transform,
+        g,
+    );
+}
+
+// Backgrounds that take turns, cross-fading into each other
+struct Backgrounds {
+    textures: Vec<G2dTexture>,
+    current: usize,
+    ticks: u64,
+    interval: u64,
+}
+
+impl Backgrounds {
+    // Images that fail to load are skipped, the background color is used when none is left
+    fn load(paths: &[String], interval: u64, context: &mut G2dTextureContext) -> Backgrounds {
+        let textures = paths
+            .iter()
+            .filter_map(|path| {
+                println!("Loading background from '{}'", path);
+                match Texture::from_path(context, path, Flip::None, &TextureSettings::new()) {
+                    Ok(texture) => Some(texture),
+                    Err(e) => {
+                        println!("Unable to load background '{}': {}", path, e);
+                        None
+                    }
+                }
+            })
+            .collect();
+        Backgrounds {
+            textures,
+            current: 0,
+            ticks: 0,
+            interval,
+        }
+    }
+
+    fn tick(&mut self) {
+        if self.textures.len() < 2 {
+            return;
+        }
+
+        self.ticks += 1;
+        if self.ticks >= (self.interval + BACKGROUND_FADE_DURATION) * UPDATES_PER_SECOND {
+            self.current = (self.current + 1) % self.textures.len();
+            self.ticks = 0;
+        }
+    }
+
+    // Background being faded in and its opacity
+    fn fading_in(&self) -> Option<(&G2dTexture, f32)> {
+        let fade_start = self.interval * UPDATES_PER_SECOND;
+        if self.textures.len() < 2 || self.ticks < fade_start {
+            return None;
+        }
+
+        let next = &self.textures[(self.current + 1) % self.textures.len()];
+        let opacity = (self.ticks - fade_start) as f32
+            / (BACKGROUND_FADE_DURATION * UPDATES_PER_SECOND) as f32;
+        Some((next, opacity))
+    }
+}
+
+// Look of the board. Theme directory mirrors the structure of 'images/', files missing
+// in the theme are taken from 'images/'. Settings are read from the 'theme.txt' file.
+struct Theme {
+    dir: Option<String>,
+    tint: [f32; 4],
+    colors: HashMap<TileColor, [f32; 4]>,
+    carrier_offset: [f64; 2],
+    background: Option<String>,
+    background_color: [f32; 4],
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            dir: None,
+            tint: TILE_TINT,
+            colors: HashMap::new(),
+            carrier_offset: [CARRIER_ICON_X_OFFSET, CARRIER_ICON_Y_OFFSET],
+            background: None,
+            background_color: BACKGROUND_COLOR,
+        }
+    }
+}
+
+impl Theme {
+    fn from_args() -> Result<Theme> {
+        match arg_value("--theme") {
+            Some(name) => Theme::load(&name),
+            None => Ok(Theme::default()),
+        }
+    }
+
+    fn load(name: &str) -> Result<Theme> {
+        let dir = format!("{}{}/", THEMES_DIR, name);
+        let file = format!("{}{}", dir, THEME_FILE);
+        println!("Loading theme from '{}'", file);
+        let mut theme = Theme {
+            dir: Some(dir),
+            ..Theme::default()
+        };
+        let buffer = BufReader::new(File::open(file)?);
+        for line in buffer.lines() {
+            let line = line?;
+            if !(line.is_empty() || line.starts_with('#')) {
+                theme.apply_setting(&line);
+            }
+        }
+        Ok(theme)
+    }
+
+    fn apply_setting(&mut self, setting: &str) {
+        let mut parts = setting.split_whitespace();
+        let key = parts.next();
+        let values: Vec<&str> = parts.collect();
+        let numbers: Vec<f64> = values.iter().filter_map(|v| v.parse().ok()).collect();
+        match (key, numbers.len()) {
+            (Some("tint"), 4) => self.tint = to_color(&numbers),
+            (Some("color"), 4) if values.len() == 5 => match TileColor::from_name(values[0]) {
+                Some(color) => {
+                    self.colors.insert(color, to_color(&numbers));
+                }
+                None => println!("Unknown tile colour '{}'", values[0]),
+            },
+            (Some("background_color"), 4) => self.background_color = to_color(&numbers),
+            (Some("carrier_offset"), 2) => self.carrier_offset = [numbers[0], numbers[1]],
+            (Some("background"), _) if values.len() == 1 => {
+                self.background = match (values[0], &self.dir) {
+                    (NO_BACKGROUND, _) | (_, None) => Some(values[0].to_string()),
+                    (path, Some(dir)) => Some(format!("{}{}", dir, path)),
+                }
+            }
+            _ => println!("Unknown theme setting '{}'", setting),
+        }
+    }
+
+    // Themed version of the file from 'images/' if the theme provides it
+    fn resolve(&self, path: &str) -> String {
+        if let (Some(dir), Some(relative)) = (&self.dir, path.strip_prefix(IMAGES_DIR)) {
+            let themed = format!("{}{}", dir, relative);
+            if std::path::Path::new(&themed).exists() {
+                return themed;
+            }
+        }
+        path.to_string()
+    }
+}
+
+fn to_color(numbers: &[f64]) -> [f32; 4] {
+    [
+        numbers[0] as f32,
+        numbers[1] as f32,
+        numbers[2] as f32,
+        numbers[3] as f32,
+    ]
+}
+
+// The playlist directory given on the command line overrides the background of the theme,
+// which in turn overrides the background of the layout
+fn background_paths(layout: &LayoutSettings, theme: &Theme) -> Vec<String> {
+    if let Some(dir) = arg_value("--backgrounds") {
+        let mut paths: Vec<String> = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.extension()
+                        .and_then(|ext| ext.to_str())
+                        .is_some_and(|ext| {
+                            ["jpg", "jpeg", "png"].contains(&ext.to_lowercase().as_str())
+                        })
+                })
+                .map(|path| path.to_string_lossy().into_owned())
+                .collect(),
+            Err(e) => {
+                println!("Unable to read backgrounds from '{}': {}", dir, e);
+                Vec::new()
+            }
+        };
+        paths.sort();
+        paths
+    } else {
+        let background = theme
+            .background
+            .clone()
+            .or_else(|| layout.background.clone())
+            .unwrap_or_else(|| DEFAULT_BACKGROUND.to_string());
+        if background == NO_BACKGROUND {
+            Vec::new()
+        } else {
+            vec![theme.resolve(&background)]
+        }
+    }
 }
 
 fn is_tile_delimiter(c: char) -> bool {
@@ -445,24 +1172,160 @@ fn is_empty_payload(c: char) -> bool {
     c == EMPTY_PAYLOAD
 }
 
-fn char_to_payload(c: char) -> Option<swarm_it::Payload<TextureId>> {
+fn char_to_payload(c: char) -> Option<swarm_it::Payload<Glyph>> {
+    char_to_colored_payload(c, TileColor::Default)
+}
+
+fn char_to_colored_payload(c: char, color: TileColor) -> Option<swarm_it::Payload<Glyph>> {
     if is_empty_payload(c) {
         None
     } else {
-        Some(Payload::new(TextureId::from_char(c)))
+        Some(Payload::new(Glyph {
+            texture: TextureId::from_char(c),
+            color,
+        }))
+    }
+}
+
+// Settings given by the '@' lines of the layout file
+#[derive(Default)]
+struct LayoutSettings {
+    background: Option<String>,
+    origin: Option<[f64; 2]>,
+    pits: Vec<[f64; 2]>,
+    spawners: Vec<[f64; 2]>,
+}
+
+impl LayoutSettings {
+    fn apply_directive(&mut self, directive: &str) {
+        let mut parts = directive.splitn(2, ' ');
+        match (parts.next(), parts.next()) {
+            (Some("background"), Some(path)) => self.background = Some(path.trim().to_string()),
+            (Some("origin"), Some(position)) => {
+                let numbers: Vec<f64> = position
+                    .split_whitespace()
+                    .filter_map(|n| n.parse().ok())
+                    .collect();
+                match numbers[..] {
+                    [x, y] => self.origin = Some([x, y]),
+                    _ => println!("Invalid board origin '{}'", position),
+                }
+            }
+            (Some("pit"), Some(position)) => match exit_position(position) {
+                Some(position) => self.pits.push(position),
+                None => println!("Invalid pit position '{}'", position),
+            },
+            (Some("spawner"), Some(position)) => match exit_position(position) {
+                Some(position) => self.spawners.push(position),
+                None => println!("Invalid spawner position '{}'", position),
+            },
+            _ => println!("Unknown layout directive '{}'", directive),
+        }
+    }
+
+    // Top left corner of the tile, the board is centered unless the layout moves it
+    fn slot_position(&self, x: u32, y: u32) -> [f64; 2] {
+        let [left, top] = self
+            .origin
+            .unwrap_or([f64::from(BOARD_LEFT_MARGIN), f64::from(BOARD_TOP_MARGIN)]);
+        [
+            left + f64::from((TILE_WIDTH + TILE_SPACING) * x),
+            top + f64::from((TILE_HEIGHT + TILE_SPACING) * y),
+        ]
+    }
+
+    // Layouts that do not declare any fall back to the sides of the screen
+    fn pits(&self) -> Vec<[f64; 2]> {
+        if self.pits.is_empty() {
+            DEFAULT_EXITS
+                .iter()
+                .filter_map(|e| exit_position(e))
+                .collect()
+        } else {
+            self.pits.clone()
+        }
+    }
+
+    fn spawners(&self) -> Vec<[f64; 2]> {
+        if self.spawners.is_empty() {
+            DEFAULT_EXITS
+                .iter()
+                .filter_map(|e| exit_position(e))
+                .collect()
+        } else {
+            self.spawners.clone()
+        }
+    }
+}
+
+// Either "<x> <y>" in board pixels, or a screen edge optionally followed by
+// the distance along it, e.g. "top 400". Edges are centered by default
+fn exit_position(text: &str) -> Option<[f64; 2]> {
+    let width = f64::from(SCREEN_SIZE_NATIVE[0]);
+    let height = f64::from(SCREEN_SIZE_NATIVE[1]);
+    let mut parts = text.split_whitespace();
+    let first = parts.next()?;
+    let second = parts.next().map(str::parse::<f64>);
+    if parts.next().is_some() {
+        return None;
+    }
+
+    let along = |default: f64| match second {
+        Some(Ok(value)) => Some(value),
+        Some(Err(_)) => None,
+        None => Some(default),
+    };
+    match first {
+        "left" => Some([-EXIT_MARGIN, along(height / 2.0)?]),
+        "right" => Some([width + EXIT_MARGIN, along(height / 2.0)?]),
+        "top" => Some([along(width / 2.0)?, -EXIT_MARGIN]),
+        "bottom" => Some([along(width / 2.0)?, height + EXIT_MARGIN]),
+        x => Some([x.parse().ok()?, second?.ok()?]),
     }
 }
 
-fn load_layout(game: &mut MyGameType, id: u32) -> Result<()> {
-    let file = format!("layouts/layout{}.txt", id);
+fn add_pits_and_spawners(game: &mut MyGameType, settings: &LayoutSettings) {
+    settings.pits().iter().for_each(|pos| {
+        game.add_slot(make_slot_pit!(pos[0], pos[1]));
+    });
+    settings.spawners().iter().for_each(|pos| {
+        game.add_slot(make_slot_spawner!(pos[0], pos[1]));
+    });
+}
+
+struct LayoutCell {
+    x: u32,
+    y: u32,
+    source: Option<swarm_it::Payload<Glyph>>,
+    target: Option<swarm_it::Payload<Glyph>>,
+}
+
+struct Layout {
+    settings: LayoutSettings,
+    cells: Vec<LayoutCell>,
+}
+
+fn layout_path(id: u32) -> String {
+    format!("{}layout{}.txt", LAYOUTS_DIR, id)
+}
+
+fn read_layout(file: &str) -> Result<Layout> {
     println!("Loading layout from '{}'", file);
     let file = File::open(file)?;
     let mut buffer = BufReader::new(file);
+    let mut settings = LayoutSettings::default();
+    let mut cells = Vec::new();
     buffer
         .by_ref()
         .lines()
         .filter(|l| match l {
-            Ok(line) => !(line.is_empty() || line.starts_with('#')),
+            Ok(line) => match line.strip_prefix(LAYOUT_DIRECTIVE) {
+                Some(directive) => {
+                    settings.apply_directive(directive);
+                    false
+                }
+                None => !(line.is_empty() || line.starts_with('#')),
+            },
             Err(_) => panic!("How come?"),
         })
         .enumerate()
@@ -478,279 +1341,2697 @@ fn load_layout(game: &mut MyGameType, id: u32) -> Result<()> {
                         payload_being_set = char_to_payload(c);
                         setting_source_cargo = false;
                     } else {
-                        game.add_slot(Slot::new(
-                            f64::from(
-                                BOARD_LEFT_MARGIN + (TILE_WIDTH + TILE_SPACING) * (x / 2) as u32,
-                            ),
-                            f64::from(BOARD_TOP_MARGIN + (TILE_HEIGHT + TILE_SPACING) * y as u32),
-                            payload_being_set,
-                            char_to_payload(c),
-                            swarm_it::SlotKind::CLASSIC,
-                        ));
+                        cells.push(LayoutCell {
+                            x: (x / 2) as u32,
+                            y: y as u32,
+                            source: payload_being_set,
+                            target: char_to_payload(c),
+                        });
                         setting_source_cargo = true;
                     };
                 })
         });
 
-    Ok(())
+    Ok(Layout { settings, cells })
 }
 
-fn row_start_index(row: u32) -> u32 {
-    row * TILES_PER_ROW
-}
+fn load_layout(game: &mut MyGameType, id: u32) -> Result<Layout> {
+    let layout = read_layout(&layout_path(id))?;
+    layout.cells.iter().for_each(|cell| {
+        let [x, y] = layout.settings.slot_position(cell.x, cell.y);
+        game.add_slot(Slot::new(
+            x,
+            y,
+            cell.source,
+            cell.target,
+            swarm_it::SlotKind::CLASSIC,
+        ));
+    });
 
-fn row_end_index(row: u32) -> u32 {
-    row_start_index(row) + TILES_PER_ROW
+    Ok(layout)
 }
 
-fn slot_index(x: u32, y: u32) -> usize {
-    (y * TILES_PER_ROW + x) as usize
+// The timetable keeps running, so the reloaded layout only moves the slots with whatever
+// they show and wait for to their new positions
+fn move_slots(game: &mut MyGameType, layout: &Layout) {
+    let slots = game.get_slots_mut();
+    layout.cells.iter().for_each(|cell| {
+        if cell.x < TILES_PER_ROW && cell.y < TILES_PER_COLUMN {
+            let index = slot_index(cell.x, cell.y);
+            let [x, y] = layout.settings.slot_position(cell.x, cell.y);
+            let payloads = slots[index].get_payloads();
+            slots[index] = Slot::new(x, y, payloads[0], payloads[1], SlotKind::CLASSIC);
+        }
+    });
 }
 
-fn fill_row_with_text(game: &mut MyGameType, row: u32, text: &str, target_only: bool) {
-    let slots = game.get_slots_mut();
-    let start_index = row_start_index(row);
-    let end_index = row_end_index(row);
-    let mut last_name_index = 0;
-    text.chars()
-        .enumerate()
-        .take(MAX_STATION_NAME_LENGTH)
-        .for_each(|(i, v)| {
-            if target_only {
-                slots[start_index as usize + i].set_target_payload(char_to_payload(v));
-            } else {
-                slots[start_index as usize + i].set_payloads(char_to_payload(v));
-            }
-            last_name_index = i;
-        });
-    for slot in slots
-        .iter_mut()
-        .take(end_index as usize)
-        .skip(start_index as usize + last_name_index + 1)
+// Busy carriers hold slot indices and reservations, which do not survive rebuilt slots, so
+// the reloaded layout and its exits wait until the swarm reports every carrier idle
+fn apply_pending_layout(
+    game: &mut MyGameType,
+    pending: &mut Option<Layout>,
+    board_slots: usize,
+) -> Option<LayoutSettings> {
+    if !game
+        .get_carriers()
+        .iter()
+        .all(|c| c.get_state() == swarm_it::State::IDLE)
     {
-        if target_only {
-            slot.set_target_payload(char_to_payload(EMPTY_PAYLOAD));
-        } else {
-            slot.set_payloads(char_to_payload(EMPTY_PAYLOAD));
-        }
+        return None;
     }
+    let layout = pending.take()?;
+    move_slots(game, &layout);
+
+    // Pits and spawners follow the board slots, so they are simply added again
+    game.get_slots_mut().truncate(board_slots);
+    add_pits_and_spawners(game, &layout.settings);
+    game.slot_data_changed();
+    Some(layout.settings)
 }
 
-fn fill_with_station_names(game: &mut MyGameType, stations: &[String]) {
-    for i in 0..TILES_PER_COLUMN {
-        fill_row_with_text(game, i, get_random_station_name(stations), false);
-    }
+// Polls modification times of all the files in the watched directories, so new files are
+// picked up as well. No need for anything fancier
+struct FileWatcher {
+    dirs: Vec<String>,
+    files: HashMap<String, Option<std::time::SystemTime>>,
+    ticks_left: u64,
 }
 
-fn fill_row_departure_time(
-    game: &mut MyGameType,
+impl FileWatcher {
+    fn new(dirs: &[String]) -> FileWatcher {
+        let mut watcher = FileWatcher {
+            dirs: Vec::new(),
+            files: HashMap::new(),
+            ticks_left: HOT_RELOAD_INTERVAL * UPDATES_PER_SECOND,
+        };
+        dirs.iter().for_each(|dir| watcher.watch(dir));
+        watcher
+    }
+
+    // Files already present are not reported as changed
+    fn watch(&mut self, dir: &str) {
+        if self.dirs.iter().any(|d| d == dir) {
+            return;
+        }
+        self.dirs.push(dir.to_string());
+        for path in list_files(dir) {
+            let time = modification_time(&path);
+            self.files.entry(path).or_insert(time);
+        }
+    }
+
+    // Returns the files modified or created since the last poll
+    fn tick(&mut self) -> Vec<String> {
+        self.ticks_left = self.ticks_left.saturating_sub(1);
+        if self.ticks_left > 0 {
+            return Vec::new();
+        }
+
+        self.ticks_left = HOT_RELOAD_INTERVAL * UPDATES_PER_SECOND;
+        let mut changed: Vec<String> = self
+            .files
+            .iter_mut()
+            .filter_map(|(path, time)| {
+                let current = modification_time(path);
+                if current != *time {
+                    *time = current;
+                    Some(path.clone())
+                } else {
+                    None
+                }
+            })
+            .collect();
+        for dir in &self.dirs {
+            for path in list_files(dir) {
+                if !self.files.contains_key(&path) {
+                    self.files.insert(path.clone(), modification_time(&path));
+                    changed.push(path);
+                }
+            }
+        }
+        changed
+    }
+}
+
+fn list_files(dir: &str) -> Vec<String> {
+    let mut files = Vec::new();
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        for path in entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+        {
+            let path = path.to_string_lossy().into_owned();
+            if std::path::Path::new(&path).is_dir() {
+                files.extend(list_files(&path));
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    files
+}
+
+fn parent_dir(path: &str) -> String {
+    std::path::Path::new(path)
+        .parent()
+        .map_or(String::from("."), |dir| dir.to_string_lossy().into_owned())
+}
+
+fn modification_time(path: &str) -> Option<std::time::SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+// Carrier sprites are reloaded too, a frame added right after the last one extends the animation
+fn reload_texture(
+    depot: &mut HashMap<TextureId, G2dTexture>,
+    context: &mut G2dTextureContext,
+    theme: &Theme,
+    types: &mut [CarrierType],
+    path: &str,
+) {
+    let mut ids: Vec<TextureId> = TEXTURE_REPOSITORY
+        .iter()
+        .filter(|x| theme.resolve(x.path) == path)
+        .map(|x| x.id)
+        .collect();
+    for (kind, carrier_type) in types.iter_mut().enumerate() {
+        let dir = match &carrier_type.sprites {
+            Some(dir) => dir,
+            None => continue,
+        };
+        let frame = (0..CARRIER_SPRITE_MAX_FRAMES)
+            .find(|frame| theme.resolve(&carrier_sprite_path(dir, *frame)) == path);
+        // Type without any sprites of its own uses the default frames until the first one appears
+        let loaded = carrier_type
+            .frames
+            .iter()
+            .filter(|id| matches!(id, TextureId::CarrierSprite(..)))
+            .count();
+        match frame {
+            Some(frame) if frame < loaded => ids.push(TextureId::CarrierSprite(kind, frame)),
+            Some(frame) if frame == loaded => {
+                if loaded == 0 {
+                    carrier_type.frames.clear();
+                }
+                ids.push(TextureId::CarrierSprite(kind, frame));
+                carrier_type
+                    .frames
+                    .push(TextureId::CarrierSprite(kind, frame));
+            }
+            _ => {}
+        }
+    }
+    if ids.is_empty() {
+        return;
+    }
+
+    println!("Reloading texture '{}'", path);
+    for id in ids {
+        match Texture::from_path(context, path, Flip::None, &TextureSettings::new()) {
+            Ok(texture) => {
+                depot.insert(id, texture);
+            }
+            Err(e) => println!("Unable to reload texture '{}': {}", path, e),
+        }
+    }
+}
+
+fn row_start_index(row: u32) -> u32 {
+    row * TILES_PER_ROW
+}
+
+fn row_end_index(row: u32) -> u32 {
+    row_start_index(row) + TILES_PER_ROW
+}
+
+fn slot_index(x: u32, y: u32) -> usize {
+    (y * TILES_PER_ROW + x) as usize
+}
+
+fn fill_row_with_text(
+    game: &mut MyGameType,
     row: u32,
-    time: DateTime<Utc>,
+    text: &str,
+    max_length: usize,
+    color: TileColor,
     target_only: bool,
 ) {
     let slots = game.get_slots_mut();
+    let start_index = row_start_index(row);
     let end_index = row_end_index(row);
+    let mut text_length = 0;
+    text.chars()
+        .enumerate()
+        .take(max_length)
+        .for_each(|(i, v)| {
+            if target_only {
+                slots[start_index as usize + i]
+                    .set_target_payload(char_to_colored_payload(v, color));
+            } else {
+                slots[start_index as usize + i].set_payloads(char_to_colored_payload(v, color));
+            }
+            text_length = i + 1;
+        });
+    for slot in slots
+        .iter_mut()
+        .take(end_index as usize)
+        .skip(start_index as usize + text_length)
+    {
+        if target_only {
+            slot.set_target_payload(char_to_payload(EMPTY_PAYLOAD));
+        } else {
+            slot.set_payloads(char_to_payload(EMPTY_PAYLOAD));
+        }
+    }
+}
+
+fn fill_title(game: &mut MyGameType, mode: BoardMode, now: DateTime<Local>, target_only: bool) {
+    fill_row_with_text(
+        game,
+        mode.title_row,
+        mode.title(),
+        CLOCK_COLUMN as usize,
+        TileColor::Title,
+        target_only,
+    );
+    fill_clock(game, mode, now, target_only);
+}
+
+fn fill_clock(game: &mut MyGameType, mode: BoardMode, time: DateTime<Local>, target_only: bool) {
+    let slots = game.get_slots_mut();
+    let start_index = slot_index(CLOCK_COLUMN, mode.title_row);
+    time.format(CLOCK_FORMAT)
+        .to_string()
+        .chars()
+        .enumerate()
+        .for_each(|(i, c)| {
+            if target_only {
+                slots[start_index + i]
+                    .set_target_payload(char_to_colored_payload(c, TileColor::Clock));
+            } else {
+                slots[start_index + i].set_payloads(char_to_colored_payload(c, TileColor::Clock));
+            }
+        });
+}
+
+// Tells whether carriers are done with the given rows, regardless of the rest of the board
+fn rows_settled(slots: &[swarm_it::Slot<Glyph>], rows: &[u32]) -> bool {
+    rows.iter()
+        .all(|row| (0..TILES_PER_ROW).all(|x| slot_settled(&slots[slot_index(x, *row)])))
+}
+
+fn slot_settled(slot: &swarm_it::Slot<Glyph>) -> bool {
+    let payloads = slot.get_payloads();
+    payloads[0].map(|p| p.cargo) == payloads[1].map(|p| p.cargo)
+}
+
+// Slots of the given rows still waiting for their target payload
+fn pending_slots(slots: &[swarm_it::Slot<Glyph>], rows: &[u32]) -> Vec<usize> {
+    rows.iter()
+        .flat_map(|row| (0..TILES_PER_ROW).map(move |x| slot_index(x, *row)))
+        .filter(|index| !slot_settled(&slots[*index]))
+        .collect()
+}
+
+// Split-flap unit rolling towards the target payload of its slot
+struct Flap {
+    glyph: usize,
+    color: TileColor,
+    ticks_left: u64,
+}
+
+// Alternative to the swarm: slots flip through the glyphs until they show their target payload.
+// It keeps its own copy of what the slots show, so the swarm keeps running underneath and the
+// display can be switched between the two at any time.
+struct SplitFlap {
+    glyphs: Vec<TextureId>,
+    shown: Vec<Option<Glyph>>,
+    flaps: HashMap<usize, Flap>,
+}
+
+impl SplitFlap {
+    fn new() -> SplitFlap {
+        SplitFlap {
+            glyphs: FLAP_GLYPHS.chars().map(TextureId::from_char).collect(),
+            shown: Vec::new(),
+            flaps: HashMap::new(),
+        }
+    }
+
+    // Glyphs not present on the flaps are treated as blank
+    fn glyph_index(&self, glyph: Option<Glyph>) -> usize {
+        glyph
+            .and_then(|g| self.glyphs.iter().position(|t| *t == g.texture))
+            .unwrap_or(0)
+    }
+
+    fn tick(&mut self, slots: &[swarm_it::Slot<Glyph>]) {
+        // Slots start with their source payload, just like for the swarm
+        while self.shown.len() < slots.len() {
+            let payloads = slots[self.shown.len()].get_payloads();
+            self.shown.push(payloads[0].map(|p| p.cargo));
+        }
+
+        for (index, slot) in slots.iter().enumerate() {
+            let target = slot.get_payloads()[1].map(|p| p.cargo);
+            if self.shown[index] == target {
+                self.flaps.remove(&index);
+                continue;
+            }
+
+            let target_glyph = self.glyph_index(target);
+            let start_glyph = self.glyph_index(self.shown[index]);
+            let target_color = target.map_or(TileColor::Default, |g| g.color);
+            let flap = self.flaps.entry(index).or_insert(Flap {
+                glyph: start_glyph,
+                color: target_color,
+                ticks_left: FLAP_STEP_DURATION,
+            });
+            flap.color = target_color;
+            if flap.glyph == target_glyph {
+                self.shown[index] = target;
+                self.flaps.remove(&index);
+                continue;
+            }
+
+            flap.ticks_left -= 1;
+            if flap.ticks_left == 0 {
+                flap.glyph = (flap.glyph + 1) % self.glyphs.len();
+                flap.ticks_left = FLAP_STEP_DURATION;
+            }
+        }
+    }
+
+    fn rows_settled(&self, slots: &[swarm_it::Slot<Glyph>], rows: &[u32]) -> bool {
+        rows.iter().all(|row| {
+            (0..TILES_PER_ROW).all(|x| {
+                let index = slot_index(x, *row);
+                let target = slots[index].get_payloads()[1].map(|p| p.cargo);
+                self.shown.get(index) == Some(&target)
+            })
+        })
+    }
+
+    fn shown_glyph(&self, index: usize) -> Option<Glyph> {
+        self.shown.get(index).copied().flatten()
+    }
+
+    // Glyph currently shown by the flapping slot and the progress of the flip in 0.0..1.0
+    fn flapping_glyph(&self, index: usize) -> Option<(Glyph, f64)> {
+        self.flaps.get(&index).map(|flap| {
+            (
+                Glyph {
+                    texture: self.glyphs[flap.glyph],
+                    color: flap.color,
+                },
+                1.0 - flap.ticks_left as f64 / FLAP_STEP_DURATION as f64,
+            )
+        })
+    }
+}
+
+fn fill_header(game: &mut MyGameType, mode: BoardMode, target_only: bool) {
+    fill_row_with_text(
+        game,
+        mode.header_row,
+        &mode.header(),
+        TILES_PER_ROW as usize,
+        TileColor::Header,
+        target_only,
+    );
+}
+
+fn fill_row_time(
+    game: &mut MyGameType,
+    row: u32,
+    column: u32,
+    time: DateTime<Local>,
+    color: TileColor,
+    target_only: bool,
+) {
+    let slots = game.get_slots_mut();
+    let start_index = row_start_index(row) + column;
 
     if target_only {
-        slots[(end_index - 2) as usize].set_target_payload(char_to_payload(
+        slots[(start_index + 3) as usize].set_target_payload(char_to_colored_payload(
             char::from_digit(time.minute() / 10, 10).unwrap(),
+            color,
         ));
-        slots[(end_index - 1) as usize].set_target_payload(char_to_payload(
+        slots[(start_index + 4) as usize].set_target_payload(char_to_colored_payload(
             char::from_digit(time.minute() % 10, 10).unwrap(),
+            color,
         ));
 
-        slots[(end_index - 5) as usize].set_target_payload(char_to_payload(
+        slots[start_index as usize].set_target_payload(char_to_colored_payload(
             char::from_digit(time.hour() / 10, 10).unwrap(),
+            color,
         ));
-        slots[(end_index - 4) as usize].set_target_payload(char_to_payload(
+        slots[(start_index + 1) as usize].set_target_payload(char_to_colored_payload(
             char::from_digit(time.hour() % 10, 10).unwrap(),
+            color,
         ));
     } else {
-        slots[(end_index - 2) as usize].set_payloads(char_to_payload(
+        slots[(start_index + 3) as usize].set_payloads(char_to_colored_payload(
             char::from_digit(time.minute() / 10, 10).unwrap(),
+            color,
         ));
-        slots[(end_index - 1) as usize].set_payloads(char_to_payload(
+        slots[(start_index + 4) as usize].set_payloads(char_to_colored_payload(
             char::from_digit(time.minute() % 10, 10).unwrap(),
+            color,
         ));
 
-        slots[(end_index - 5) as usize].set_payloads(char_to_payload(
+        slots[start_index as usize].set_payloads(char_to_colored_payload(
             char::from_digit(time.hour() / 10, 10).unwrap(),
+            color,
         ));
-        slots[(end_index - 4) as usize].set_payloads(char_to_payload(
+        slots[(start_index + 1) as usize].set_payloads(char_to_colored_payload(
             char::from_digit(time.hour() % 10, 10).unwrap(),
+            color,
         ));
     }
 }
 
-fn increase_departure_time(time: DateTime<Utc>) -> DateTime<Utc> {
-    let mut rng = rand::thread_rng();
+// Column of the HH:MM field, the time and its colour.
+// Trains delayed for too long have no expected time, they are shown as DELAYED instead
+fn train_time_fields(mode: BoardMode, train: &Train) -> Vec<(u32, DateTime<Local>, TileColor)> {
+    match mode.kind {
+        BoardKind::Departures => vec![(DEPARTURE_TIME_COLUMN, train.time, TileColor::Time)],
+        BoardKind::Arrivals if train.delay_unknown() => {
+            vec![(SCHEDULED_ARRIVAL_COLUMN, train.time, TileColor::Time)]
+        }
+        BoardKind::Arrivals => vec![
+            (SCHEDULED_ARRIVAL_COLUMN, train.time, TileColor::Time),
+            (
+                EXPECTED_ARRIVAL_COLUMN,
+                train.expected,
+                if train.expected > train.time {
+                    TileColor::Late
+                } else {
+                    TileColor::OnTime
+                },
+            ),
+        ],
+    }
+}
+
+fn fill_row_train_times(
+    game: &mut MyGameType,
+    mode: BoardMode,
+    row: u32,
+    train: &Train,
+    target_only: bool,
+) {
+    for (column, time, color) in train_time_fields(mode, train) {
+        fill_row_time(game, row, column, time, color, target_only);
+
+        // Take special care about the HH:MM separator
+        let slot = &mut game.get_slots_mut()[slot_index(column + 2, row)];
+        if target_only {
+            slot.set_target_payload(char_to_colored_payload(':', color));
+        } else {
+            slot.set_payloads(char_to_colored_payload(':', color));
+        }
+    }
+}
+
+fn increase_departure_time(time: DateTime<Local>, rng: &mut ChaChaRng) -> DateTime<Local> {
     time.add(Duration::minutes(
         rng.gen_range(TIME_DIFFERENCE_MINIMUM, TIME_DIFFERENCE_MAXMIMUM),
     ))
 }
 
-fn fill_departure_times(game: &mut MyGameType) -> DateTime<Utc> {
-    let mut departure_time = Utc::now();
-    for i in 0..TILES_PER_COLUMN {
-        fill_row_departure_time(game, i, departure_time, false);
-        departure_time = increase_departure_time(departure_time);
-    }
-    departure_time
+fn expected_arrival_time(scheduled: DateTime<Local>, rng: &mut ChaChaRng) -> DateTime<Local> {
+    if rng.gen_bool(ARRIVAL_DELAY_PROBABILITY) {
+        scheduled.add(Duration::minutes(rng.gen_range(1, ARRIVAL_DELAY_MAXIMUM)))
+    } else {
+        scheduled
+    }
+}
+
+struct Train {
+    station: String,
+    platform: u32,
+    time: DateTime<Local>,
+    expected: DateTime<Local>,
+    calling_points: String,
+}
+
+impl Train {
+    fn delay_unknown(&self) -> bool {
+        self.expected >= self.time.add(Duration::minutes(ARRIVAL_DELAY_UNKNOWN))
+    }
+
+    fn random(
+        mode: BoardMode,
+        time: DateTime<Local>,
+        station_names: &[String],
+        rng: &mut ChaChaRng,
+    ) -> Train {
+        Train {
+            station: get_random_station_name(station_names, rng).to_string(),
+            platform: rng.gen_range(1, PLATFORM_MAXIMUM + 1),
+            time,
+            expected: match mode.kind {
+                BoardKind::Departures => time,
+                BoardKind::Arrivals => expected_arrival_time(time, rng),
+            },
+            calling_points: if mode.has_calling_points() {
+                synthesize_calling_points(station_names, rng)
+            } else {
+                String::new()
+            },
+        }
+    }
+}
+
+// Schedule covers the next SCHEDULE_HORIZON hours, but always fills at least a single page
+fn create_trains(
+    mode: BoardMode,
+    station_names: &[String],
+    start_time: DateTime<Local>,
+    rng: &mut ChaChaRng,
+) -> Vec<Train> {
+    let end_time = start_time.add(Duration::hours(SCHEDULE_HORIZON));
+    let rows = mode.train_rows().len();
+    let mut trains = Vec::new();
+    let mut train_time = start_time;
+    while trains.len() < rows || train_time < end_time {
+        trains.push(Train::random(mode, train_time, station_names, rng));
+        train_time = increase_departure_time(train_time, rng);
+    }
+    trains
+}
+
+fn fill_row_with_train(
+    game: &mut MyGameType,
+    mode: BoardMode,
+    row: u32,
+    train: &Train,
+    target_only: bool,
+) {
+    fill_row_with_text(
+        game,
+        row,
+        &train.station,
+        mode.max_station_name_length(),
+        TileColor::Default,
+        target_only,
+    );
+    fill_field(
+        game,
+        row,
+        mode.platform_column(),
+        &format!("{:>2}", train.platform),
+        TileColor::Platform,
+        target_only,
+    );
+    fill_row_train_times(game, mode, row, train, target_only);
+    if mode.kind == BoardKind::Arrivals && train.delay_unknown() {
+        fill_field(
+            game,
+            row,
+            EXPECTED_ARRIVAL_COLUMN,
+            DELAYED_TEXT,
+            TileColor::Delayed,
+            target_only,
+        );
+    }
+}
+
+// Writes the text from the given column on, the rest of the row is left as it is
+fn fill_field(
+    game: &mut MyGameType,
+    row: u32,
+    column: u32,
+    text: &str,
+    color: TileColor,
+    target_only: bool,
+) {
+    let slots = game.get_slots_mut();
+    let start_index = slot_index(column, row);
+    text.chars()
+        .take((TILES_PER_ROW - column) as usize)
+        .enumerate()
+        .for_each(|(i, c)| {
+            if target_only {
+                slots[start_index + i].set_target_payload(char_to_colored_payload(c, color));
+            } else {
+                slots[start_index + i].set_payloads(char_to_colored_payload(c, color));
+            }
+        });
+}
+
+// Fills the row with the train at the given position of the schedule, if any
+fn fill_train_row(
+    game: &mut MyGameType,
+    mode: BoardMode,
+    row: u32,
+    trains: &[Train],
+    cancellations: &[Cancellation],
+    position: usize,
+    target_only: bool,
+) {
+    match trains.get(position) {
+        Some(train) => {
+            fill_row_with_train(game, mode, row, train, target_only);
+            if cancellations.iter().any(|c| c.position == position) {
+                fill_row_cancelled(game, mode, row);
+            }
+        }
+        None => fill_row_with_text(game, row, "", 0, TileColor::Default, target_only),
+    }
+}
+
+fn page_count(mode: BoardMode, trains: &[Train]) -> usize {
+    let rows = mode.train_rows().len();
+    trains.len().div_ceil(rows)
+}
+
+fn top_train(mode: BoardMode, trains: &[Train], page: usize) -> Option<&Train> {
+    trains.get(page * mode.train_rows().len())
+}
+
+fn fill_page(
+    game: &mut MyGameType,
+    mode: BoardMode,
+    trains: &[Train],
+    cancellations: &[Cancellation],
+    page: usize,
+    target_only: bool,
+) {
+    let train_rows = mode.train_rows();
+    let page_start = page * train_rows.len();
+    for (i, row) in train_rows.iter().enumerate() {
+        fill_train_row(
+            game,
+            mode,
+            *row,
+            trains,
+            cancellations,
+            page_start + i,
+            target_only,
+        );
+    }
+    fill_page_indicator(game, mode, page, page_count(mode, trains), target_only);
+}
+
+fn fill_page_indicator(
+    game: &mut MyGameType,
+    mode: BoardMode,
+    page: usize,
+    pages: usize,
+    target_only: bool,
+) {
+    if pages < 2 {
+        return;
+    }
+
+    // Indicator is moved to the left rather than cut when it does not fit
+    let text = format!("PAGE {} OF {}", page + 1, pages);
+    let [row, column] = mode.page_indicator;
+    let column = column.min(TILES_PER_ROW - text.len() as u32);
+    fill_field(game, row, column, &text, TileColor::Title, target_only);
+}
+
+// Calling points of the top departure, scrolled when they don't fit in a row
+struct CallingPoints {
+    text: String,
+    offset: usize,
+    ticks_left: u64,
+}
+
+fn synthesize_calling_points(station_names: &[String], rng: &mut ChaChaRng) -> String {
+    let count = rng.gen_range(CALLING_POINTS_MINIMUM, CALLING_POINTS_MAXIMUM + 1);
+    let stations: Vec<&str> = (0..count)
+        .map(|_| get_random_station_name(station_names, rng))
+        .collect();
+    format!(
+        "{}{}",
+        CALLING_POINTS_PREFIX,
+        stations.join(CALLING_POINTS_SEPARATOR)
+    )
+}
+
+impl CallingPoints {
+    fn new(text: &str) -> CallingPoints {
+        CallingPoints {
+            text: text.to_string(),
+            offset: 0,
+            ticks_left: CALLING_POINTS_SCROLL_INTERVAL * UPDATES_PER_SECOND,
+        }
+    }
+
+    fn needs_scrolling(&self) -> bool {
+        self.text.chars().count() > TILES_PER_ROW as usize
+    }
+
+    fn visible_text(&self) -> String {
+        if !self.needs_scrolling() {
+            return self.text.clone();
+        }
+        self.text
+            .chars()
+            .chain(CALLING_POINTS_GAP.chars())
+            .cycle()
+            .skip(self.offset)
+            .take(TILES_PER_ROW as usize)
+            .collect()
+    }
+
+    fn scroll(&mut self) {
+        let length = self.text.chars().count() + CALLING_POINTS_GAP.chars().count();
+        self.offset = (self.offset + 1) % length;
+        self.ticks_left = CALLING_POINTS_SCROLL_INTERVAL * UPDATES_PER_SECOND;
+    }
+}
+
+fn fill_calling_points(
+    game: &mut MyGameType,
+    mode: BoardMode,
+    calling_points: &CallingPoints,
+    target_only: bool,
+) {
+    fill_row_with_text(
+        game,
+        mode.calling_points_row(),
+        &calling_points.visible_text(),
+        TILES_PER_ROW as usize,
+        TileColor::CallingPoints,
+        target_only,
+    );
+}
+
+// Brings the calling points of the train that appeared at the top of the page
+fn refresh_calling_points(
+    game: &mut MyGameType,
+    mode: BoardMode,
+    calling_points: &mut Option<CallingPoints>,
+    top_train: Option<&Train>,
+) {
+    if let Some(calling_points) = calling_points {
+        let text = top_train.map_or("", |train| &train.calling_points);
+        if calling_points.text != text {
+            *calling_points = CallingPoints::new(text);
+            fill_calling_points(game, mode, calling_points, true);
+            game.slot_data_changed();
+        }
+    }
+}
+
+// Departure that has been cancelled and waits to be removed from the board.
+// The countdown only runs while the CANCELLED row is on the board and settled.
+struct Cancellation {
+    position: usize,
+    ticks_left: u64,
+}
+
+// Moves all trains below the given position one train row up
+fn remove_row_and_compact(
+    slots: &mut [swarm_it::Slot<Glyph>],
+    train_rows: &[u32],
+    position: usize,
+) {
+    for i in position..train_rows.len() - 1 {
+        for x in 0..TILES_PER_ROW {
+            let payloads = slots[slot_index(x, train_rows[i + 1])].get_payloads();
+            slots[slot_index(x, train_rows[i])].set_target_payload(payloads[0]);
+        }
+    }
+}
+
+// Status takes the rest of the row
+fn fill_row_cancelled(game: &mut MyGameType, mode: BoardMode, row: u32) {
+    let column = mode.status_column();
+    let text = format!(
+        "{:<width$}",
+        CANCELLED_TEXT,
+        width = (TILES_PER_ROW - column) as usize
+    );
+    fill_field(game, row, column, &text, TileColor::Cancelled, true);
+}
+
+// Arrived train is due to leave the board, arrivals do not wait for a key press
+fn train_arrived(mode: BoardMode, trains: &[Train], now: DateTime<Local>) -> bool {
+    mode.kind == BoardKind::Arrivals
+        && trains
+            .first()
+            .is_some_and(|train| train.expected + Duration::minutes(ARRIVAL_DWELL_TIME) <= now)
+}
+
+// Only the trains on the current page are candidates, so the cancellation is visible
+fn cancel_random_train(
+    game: &mut MyGameType,
+    mode: BoardMode,
+    trains: &[Train],
+    page: usize,
+    cancellations: &mut Vec<Cancellation>,
+    display_ticks: u64,
+    rng: &mut ChaChaRng,
+) -> bool {
+    let train_rows = mode.train_rows();
+    let page_start = page * train_rows.len();
+    let candidates: Vec<usize> = (page_start..page_start + train_rows.len())
+        .filter(|position| *position < trains.len())
+        .filter(|position| !cancellations.iter().any(|c| c.position == *position))
+        .collect();
+    if candidates.is_empty() {
+        return false;
+    }
+
+    let position = candidates[rng.gen_range(0, candidates.len())];
+    fill_row_cancelled(game, mode, train_rows[position - page_start]);
+    game.slot_data_changed();
+    cancellations.push(Cancellation {
+        position,
+        ticks_left: display_ticks,
+    });
+    true
+}
+
+fn tick_cancellations(
+    settled_rows: &[u32],
+    mode: BoardMode,
+    page: usize,
+    cancellations: &mut [Cancellation],
+) {
+    let train_rows = mode.train_rows();
+    let page_start = page * train_rows.len();
+    for cancellation in cancellations.iter_mut() {
+        let row = cancellation
+            .position
+            .checked_sub(page_start)
+            .and_then(|i| train_rows.get(i));
+        if row.is_some_and(|row| settled_rows.contains(row)) {
+            cancellation.ticks_left = cancellation.ticks_left.saturating_sub(1);
+        }
+    }
+}
+
+// Keeps the pending cancellations in sync with the trains that moved up
+fn compact_cancellations(cancellations: &mut Vec<Cancellation>, removed_position: usize) {
+    cancellations.retain(|c| c.position != removed_position);
+    cancellations
+        .iter_mut()
+        .filter(|c| c.position > removed_position)
+        .for_each(|c| c.position -= 1);
+}
+
+// Used for both departing and arriving trains, the top row leaves the board
+#[allow(clippy::too_many_arguments)]
+fn train_departure(
+    game: &mut MyGameType,
+    mode: BoardMode,
+    trains: &mut Vec<Train>,
+    page: usize,
+    cancellations: &[Cancellation],
+    station_names: &[String],
+    now: DateTime<Local>,
+    rng: &mut ChaChaRng,
+) {
+    train_removal(
+        game,
+        mode,
+        trains,
+        page,
+        cancellations,
+        0,
+        station_names,
+        now,
+        rng,
+    )
+}
+
+// Removes the train from the schedule and compacts the visible rows if they are affected.
+// Cancellations must already be compacted for the removed position. The board time only
+// matters if no train is left to follow.
+#[allow(clippy::too_many_arguments)]
+fn train_removal(
+    game: &mut MyGameType,
+    mode: BoardMode,
+    trains: &mut Vec<Train>,
+    page: usize,
+    cancellations: &[Cancellation],
+    position: usize,
+    station_names: &[String],
+    now: DateTime<Local>,
+    rng: &mut ChaChaRng,
+) {
+    trains.remove(position);
+    let next_time = increase_departure_time(trains.last().map_or(now, |t| t.time), rng);
+    trains.push(Train::random(mode, next_time, station_names, rng));
+
+    let train_rows = mode.train_rows();
+    let page_start = page * train_rows.len();
+    let page_end = page_start + train_rows.len();
+    if position < page_end {
+        remove_row_and_compact(
+            game.get_slots_mut(),
+            &train_rows,
+            position.saturating_sub(page_start),
+        );
+        fill_train_row(
+            game,
+            mode,
+            *train_rows.last().unwrap(),
+            trains,
+            cancellations,
+            page_end - 1,
+            true,
+        );
+    }
+    game.slot_data_changed();
+}
+
+// Horizontal scale of the sprite (1.0 faces right, -1.0 faces left) and its tilt
+#[derive(Copy, Clone)]
+struct Orientation {
+    facing: f64,
+    tilt: f64,
+}
+
+impl Orientation {
+    // Tilt follows the heading, but is clamped so carriers never fly upside down
+    fn from_angle(angle: f64) -> Orientation {
+        let facing = if is_facing_left(angle) { -1.0 } else { 1.0 };
+        let heading = if facing < 0.0 {
+            angle - std::f64::consts::PI
+        } else {
+            angle
+        };
+        let tilt = heading.sin().atan2(heading.cos());
+        Orientation {
+            facing,
+            tilt: tilt.clamp(-CARRIER_MAX_TILT, CARRIER_MAX_TILT),
+        }
+    }
+
+    fn approach(&mut self, target: Orientation, dt: f64) {
+        let step = |from: f64, to: f64, rate: f64| from + (to - from).clamp(-rate, rate);
+        self.facing = step(self.facing, target.facing, CARRIER_TURN_RATE * dt);
+        self.tilt = step(self.tilt, target.tilt, CARRIER_TILT_RATE * dt);
+    }
+}
+
+// Everything kept about a single carrier, read by the animation, effects, metrics and overlay
+struct CarrierState {
+    kind: usize,
+    position: [f64; 2],
+    // Distance travelled during the last simulation step
+    velocity: [f64; 2],
+    carrying: Option<Glyph>,
+    was_carrying: Option<Glyph>,
+    // What the swarm has the carrier doing, a carrier turning towards its target stays put
+    swarm_state: swarm_it::State,
+    trail: VecDeque<[f64; 2]>,
+    // Each carrier flaps at its own phase, faster birds flap faster
+    phase: f64,
+    orientation: Option<Orientation>,
+}
+
+impl CarrierState {
+    fn speed(&self) -> f64 {
+        (self.velocity[0].powi(2) + self.velocity[1].powi(2)).sqrt()
+    }
+
+    fn picked_up(&self) -> bool {
+        self.was_carrying.is_none() && self.carrying.is_some()
+    }
+
+    fn dropped(&self) -> Option<Glyph> {
+        match (self.was_carrying, self.carrying) {
+            (Some(glyph), None) => Some(glyph),
+            _ => None,
+        }
+    }
+
+    fn frame(&self, frames: &[TextureId]) -> TextureId {
+        frames[self.phase as usize % frames.len()]
+    }
+
+    // Sprite is turned and tilted around its center
+    fn orient(&self, context: Context, size: [f64; 2]) -> Context {
+        match self.orientation {
+            Some(orientation) => context
+                .trans(size[0] / 2.0, size[1] / 2.0)
+                .rot_rad(orientation.tilt)
+                .scale(orientation.facing, 1.0)
+                .trans(-size[0] / 2.0, -size[1] / 2.0),
+            None => context,
+        }
+    }
+}
+
+// State of each carrier, in the same order as the carriers of the swarm
+struct CarrierStates {
+    rng: ChaChaRng,
+    states: Vec<CarrierState>,
+}
+
+impl CarrierStates {
+    fn new(rng: ChaChaRng) -> CarrierStates {
+        CarrierStates {
+            rng,
+            states: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, kind: usize, position: [f64; 2]) {
+        let phase = self.rng.gen_range(0.0, CARRIER_FRAMES.len() as f64);
+        self.states.push(CarrierState {
+            kind,
+            position,
+            velocity: [0.0, 0.0],
+            carrying: None,
+            was_carrying: None,
+            swarm_state: swarm_it::State::IDLE,
+            trail: VecDeque::new(),
+            phase,
+            orientation: None,
+        });
+    }
+
+    fn remove(&mut self, index: usize) -> Option<CarrierState> {
+        if index < self.states.len() {
+            Some(self.states.remove(index))
+        } else {
+            None
+        }
+    }
+
+    fn tick(&mut self, carriers: &[Carrier<Glyph>], dt: f64) {
+        for (state, c) in self.states.iter_mut().zip(carriers) {
+            let pos = c.get_position();
+            state.velocity = [pos.x - state.position[0], pos.y - state.position[1]];
+            state.position = [pos.x, pos.y];
+            state.was_carrying = state.carrying;
+            state.carrying = c.get_payload().map(|p| p.cargo);
+            state.swarm_state = c.get_state();
+            state.trail.push_front(state.position);
+            state.trail.truncate(TRAIL_LENGTH);
+
+            // Phase counts frames, the fraction is the progress towards the next one
+            state.phase +=
+                dt * (CARRIER_ANIM_FRAME_RATE + state.speed() * CARRIER_ANIM_SPEED_FACTOR);
+            let target = Orientation::from_angle(c.get_angle());
+            match state.orientation.as_mut() {
+                Some(orientation) => orientation.approach(target, dt),
+                None => state.orientation = Some(target),
+            }
+        }
+    }
+}
+
+struct Particle {
+    position: [f64; 2],
+    velocity: [f64; 2],
+    ticks_left: u32,
+    color: [f32; 4],
+}
+
+// Fading trails behind the carriers and bursts where payloads are dropped
+struct Effects {
+    rng: ChaChaRng,
+    enabled: bool,
+    particles: Vec<Particle>,
+}
+
+impl Effects {
+    fn new(rng: ChaChaRng) -> Effects {
+        Effects {
+            rng,
+            enabled: arg_flag("--effects"),
+            particles: Vec::new(),
+        }
+    }
+
+    fn tick(&mut self, carriers: &[CarrierState], theme: &Theme) {
+        if !self.enabled {
+            return;
+        }
+
+        // Carrier that has just let go of its payload dropped it into a slot
+        for state in carriers {
+            if let Some(dropped) = state.dropped() {
+                self.burst(state.position, dropped.color.rgba(theme));
+            }
+        }
+
+        self.particles.iter_mut().for_each(|p| {
+            p.position[0] += p.velocity[0];
+            p.position[1] += p.velocity[1];
+            p.ticks_left -= 1;
+        });
+        self.particles.retain(|p| p.ticks_left > 0);
+    }
+
+    fn burst(&mut self, position: [f64; 2], color: [f32; 4]) {
+        let count = PARTICLES_PER_DROP.min(MAX_PARTICLES - self.particles.len());
+        for _ in 0..count {
+            let angle = self.rng.gen_range(0.0, 2.0 * std::f64::consts::PI);
+            let speed = self.rng.gen_range(0.3, 1.0) * PARTICLE_SPEED;
+            self.particles.push(Particle {
+                position: [
+                    position[0] + f64::from(TILE_WIDTH) / 2.0,
+                    position[1] + f64::from(TILE_HEIGHT) / 2.0,
+                ],
+                velocity: [angle.cos() * speed, angle.sin() * speed],
+                ticks_left: PARTICLE_LIFETIME,
+                color,
+            });
+        }
+    }
+
+    fn draw(&self, carriers: &[CarrierState], ctx: &Context, g: &mut G2d) {
+        let offset = [f64::from(TILE_WIDTH) / 2.0, f64::from(TILE_HEIGHT) / 2.0];
+        for trail in carriers.iter().map(|state| &state.trail) {
+            for (i, (from, to)) in trail.iter().zip(trail.iter().skip(1)).enumerate() {
+                let mut color = TRAIL_COLOR;
+                color[3] *= 1.0 - i as f32 / TRAIL_LENGTH as f32;
+                line(
+                    color,
+                    TRAIL_WIDTH,
+                    [
+                        from[0] + offset[0],
+                        from[1] + offset[1],
+                        to[0] + offset[0],
+                        to[1] + offset[1],
+                    ],
+                    ctx.transform,
+                    g,
+                );
+            }
+        }
+
+        for p in &self.particles {
+            let mut color = p.color;
+            color[3] *= p.ticks_left as f32 / PARTICLE_LIFETIME as f32;
+            rectangle(
+                color,
+                [p.position[0], p.position[1], PARTICLE_SIZE, PARTICLE_SIZE],
+                ctx.transform,
+                g,
+            );
+        }
+    }
+
+    fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.particles.clear();
+    }
+}
+
+// Frames or updates counted during the last full second
+struct RateCounter {
+    count: u32,
+    rate: u32,
+    since: Instant,
+}
+
+impl RateCounter {
+    fn new() -> RateCounter {
+        RateCounter {
+            count: 0,
+            rate: 0,
+            since: Instant::now(),
+        }
+    }
+
+    fn tick(&mut self) {
+        self.count += 1;
+        if self.since.elapsed().as_secs() >= 1 {
+            self.rate = self.count;
+            self.count = 0;
+            self.since = Instant::now();
+        }
+    }
+}
+
+// Swarm internals shown when the board stalls
+struct DebugOverlay {
+    enabled: bool,
+    updates: RateCounter,
+    frames: RateCounter,
+}
+
+impl DebugOverlay {
+    fn new() -> DebugOverlay {
+        DebugOverlay {
+            enabled: false,
+            updates: RateCounter::new(),
+            frames: RateCounter::new(),
+        }
+    }
+}
+
+fn draw_debug_text(
+    texture_depot: &HashMap<TextureId, G2dTexture>,
+    text: &str,
+    context: &Context,
+    g: &mut G2d,
+) {
+    text.chars().enumerate().for_each(|(i, c)| {
+        let context = context
+            .trans(i as f64 * f64::from(TILE_WIDTH) * DEBUG_TEXT_SCALE, 0.0)
+            .scale(DEBUG_TEXT_SCALE, DEBUG_TEXT_SCALE);
+        draw_texture(
+            texture_depot.get(&TextureId::from_char(c)),
+            [1.0, 1.0, 1.0, 1.0],
+            &context,
+            g,
+        );
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_debug_overlay(
+    overlay: &DebugOverlay,
+    game: &MyGameType,
+    carriers: &[CarrierState],
+    mode: BoardMode,
+    layout_settings: &LayoutSettings,
+    texture_depot: &HashMap<TextureId, G2dTexture>,
+    ctx: &Context,
+    g: &mut G2d,
+) {
+    let tile_center = [f64::from(TILE_WIDTH) / 2.0, f64::from(TILE_HEIGHT) / 2.0];
+    let slots = game.get_slots();
+
+    // Slots waiting for their target payload
+    let pending = pending_slots(slots, &mode.transition_rows());
+    pending.iter().for_each(|index| {
+        let pos = slots[*index].get_position();
+        Rectangle::new_border(DEBUG_PENDING_COLOR, 1.5).draw(
+            [pos.x, pos.y, f64::from(TILE_WIDTH), f64::from(TILE_HEIGHT)],
+            &ctx.draw_state,
+            ctx.transform,
+            g,
+        );
+    });
+
+    // Pits and spawners sit off screen, so their markers are kept at the board edge
+    let marker = |pos: &[f64; 2]| {
+        [
+            pos[0].clamp(0.0, f64::from(SCREEN_SIZE_NATIVE[0]) - DEBUG_MARKER_SIZE),
+            pos[1].clamp(0.0, f64::from(SCREEN_SIZE_NATIVE[1]) - DEBUG_MARKER_SIZE),
+            DEBUG_MARKER_SIZE,
+            DEBUG_MARKER_SIZE,
+        ]
+    };
+    layout_settings.pits().iter().for_each(|pos| {
+        rectangle(DEBUG_PIT_COLOR, marker(pos), ctx.transform, g);
+    });
+    layout_settings.spawners().iter().for_each(|pos| {
+        Rectangle::new_border(DEBUG_SPAWNER_COLOR, 3.0).draw(
+            marker(pos),
+            &ctx.draw_state,
+            ctx.transform,
+            g,
+        );
+    });
+
+    game.get_carriers().iter().enumerate().for_each(|(i, c)| {
+        let pos = c.get_position();
+        let center = [pos.x + tile_center[0], pos.y + tile_center[1]];
+        // Slot the carrier is heading to, or the one reserved for the payload it deals with
+        if let Some(target) = c.get_target().or_else(|| c.get_reserved_target()) {
+            let target_pos = slots[target].get_position();
+            line(
+                DEBUG_TARGET_COLOR,
+                1.0,
+                [
+                    center[0],
+                    center[1],
+                    target_pos.x + tile_center[0],
+                    target_pos.y + tile_center[1],
+                ],
+                ctx.transform,
+                g,
+            );
+        }
+        if let Some(velocity) = carriers.get(i).map(|state| state.velocity) {
+            line(
+                DEBUG_VELOCITY_COLOR,
+                2.0,
+                [
+                    center[0],
+                    center[1],
+                    center[0] + velocity[0] * DEBUG_VELOCITY_SCALE,
+                    center[1] + velocity[1] * DEBUG_VELOCITY_SCALE,
+                ],
+                ctx.transform,
+                g,
+            );
+        }
+    });
+
+    let text = format!(
+        "FPS {} UPS {} CARRIERS {} PENDING {}",
+        overlay.frames.rate,
+        overlay.updates.rate,
+        game.get_carriers().len(),
+        pending.len()
+    );
+    draw_debug_text(texture_depot, &text, &ctx.trans(10.0, 10.0), g);
+}
+
+fn load_station_names(list: &mut Vec<String>, file: &str) -> Result<()> {
+    println!("Loading stations from '{}'", file);
+    let file = File::open(file)?;
+    let buffer = BufReader::new(file);
+    buffer.lines().for_each(|line| list.push(line.unwrap()));
+
+    println!("{} station(s) loaded", list.len());
+    Ok(())
+}
+
+// Everything that changes the board and does not follow from the seed alone
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum BoardEvent {
+    Departure,
+    Cancellation,
+    ToggleCarriers,
+    ToggleDisplay,
+    AddCarrier,
+    RetireCarrier,
+    // Cancelled train leaving the schedule
+    Removal(usize),
+    // Page the board turned to
+    Page(usize),
+}
+
+impl BoardEvent {
+    fn parse(text: &str) -> Option<BoardEvent> {
+        let mut parts = text.split_whitespace();
+        let event = match parts.next()? {
+            "departure" => BoardEvent::Departure,
+            "cancellation" => BoardEvent::Cancellation,
+            "toggle_carriers" => BoardEvent::ToggleCarriers,
+            "toggle_display" => BoardEvent::ToggleDisplay,
+            "add_carrier" => BoardEvent::AddCarrier,
+            "retire_carrier" => BoardEvent::RetireCarrier,
+            "removal" => BoardEvent::Removal(parts.next()?.parse().ok()?),
+            "page" => BoardEvent::Page(parts.next()?.parse().ok()?),
+            _ => return None,
+        };
+        match parts.next() {
+            Some(_) => None,
+            None => Some(event),
+        }
+    }
+}
+
+impl std::fmt::Display for BoardEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BoardEvent::Departure => write!(f, "departure"),
+            BoardEvent::Cancellation => write!(f, "cancellation"),
+            BoardEvent::ToggleCarriers => write!(f, "toggle_carriers"),
+            BoardEvent::ToggleDisplay => write!(f, "toggle_display"),
+            BoardEvent::AddCarrier => write!(f, "add_carrier"),
+            BoardEvent::RetireCarrier => write!(f, "retire_carrier"),
+            BoardEvent::Removal(position) => write!(f, "removal {}", position),
+            BoardEvent::Page(page) => write!(f, "page {}", page),
+        }
+    }
+}
+
+// Records the board events with their simulation ticks, or plays them back.
+// The file starts with the seed and the start time, followed by "<tick> <event>" lines
+struct EventLog {
+    recording: Option<File>,
+    replay: Option<VecDeque<(u64, BoardEvent)>>,
+    seed: Option<u64>,
+    start_time: Option<DateTime<Local>>,
+    // Definitions of the carrier types of the replayed recording
+    carrier_types: Option<Vec<String>>,
+    simulated_clock: bool,
+}
+
+impl EventLog {
+    fn from_args() -> Result<EventLog> {
+        let mut log = EventLog {
+            recording: None,
+            replay: None,
+            seed: None,
+            start_time: None,
+            carrier_types: None,
+            simulated_clock: false,
+        };
+        if let Some(path) = arg_value("--replay") {
+            println!("Replaying events from '{}'", path);
+            log.load(&path)?;
+        } else if let Some(path) = arg_value("--record") {
+            println!("Recording events to '{}'", path);
+            log.recording = Some(File::create(path)?);
+        }
+        Ok(log)
+    }
+
+    fn load(&mut self, path: &str) -> Result<()> {
+        let invalid = |line: &str| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Invalid event '{}' in '{}'", line, path),
+            )
+        };
+        let mut events = VecDeque::new();
+        let mut options = Vec::new();
+        let mut carrier_types = Vec::new();
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            let mut parts = line.splitn(2, ' ');
+            match (parts.next(), parts.next()) {
+                (Some(""), None) => {}
+                (Some("seed"), Some(seed)) => {
+                    self.seed = Some(seed.parse().map_err(|_| invalid(&line))?)
+                }
+                (Some("start"), Some(time)) => {
+                    let time = DateTime::parse_from_rfc3339(time).map_err(|_| invalid(&line))?;
+                    self.start_time = Some(time.with_timezone(&Local))
+                }
+                (Some("option"), Some(option)) => {
+                    options.extend(option.split_whitespace().map(str::to_string))
+                }
+                (Some("carrier_type"), Some(definition)) => {
+                    carrier_types.push(definition.to_string())
+                }
+                (Some(tick), Some(event)) => events.push_back((
+                    tick.parse().map_err(|_| invalid(&line))?,
+                    BoardEvent::parse(event).ok_or_else(|| invalid(&line))?,
+                )),
+                _ => return Err(invalid(&line)),
+            }
+        }
+        self.replay = Some(events);
+        self.carrier_types = Some(carrier_types);
+        let _ = REPLAYED_OPTIONS.set(options);
+        Ok(())
+    }
+
+    // Clock of a recorded, replayed or otherwise reproducible run follows the ticks
+    fn start(&mut self, seed: u64, start_time: DateTime<Local>, reproducible: bool) {
+        self.simulated_clock = reproducible || self.recording.is_some() || self.replay.is_some();
+        self.header(&format!("seed {}", seed));
+        self.header(&format!("start {}", start_time.to_rfc3339()));
+        simulation_options()
+            .iter()
+            .for_each(|option| self.header(&format!("option {}", option)));
+    }
+
+    // Built-in carrier type has no definition, a recording without any uses it alone
+    fn record_carrier_types(&mut self, types: &[CarrierType]) {
+        types
+            .iter()
+            .filter_map(|carrier_type| carrier_type.definition())
+            .for_each(|definition| self.header(&format!("carrier_type {}", definition)));
+    }
+
+    fn header(&mut self, line: &str) {
+        if let Some(file) = self.recording.as_mut() {
+            if let Err(e) = writeln!(file, "{}", line) {
+                println!("Unable to record events: {}", e);
+                self.recording = None;
+            }
+        }
+    }
+
+    fn record(&mut self, tick: u64, event: BoardEvent) {
+        if let Some(file) = self.recording.as_mut() {
+            if let Err(e) = writeln!(file, "{} {}", tick, event) {
+                println!("Unable to record events: {}", e);
+                self.recording = None;
+            }
+        }
+    }
+
+    // Live input is taken again once all the events are replayed
+    fn replaying(&self) -> bool {
+        self.replay
+            .as_ref()
+            .is_some_and(|events| !events.is_empty())
+    }
+
+    // The board clock follows the ticks, otherwise the clock digits would differ between runs
+    fn board_time(&self, start_time: DateTime<Local>, tick: u64) -> DateTime<Local> {
+        if self.simulated_clock {
+            start_time.add(Duration::milliseconds(
+                (tick * 1000 / UPDATES_PER_SECOND) as i64,
+            ))
+        } else {
+            Local::now()
+        }
+    }
+
+    // Input events are applied at the start of the tick they were recorded at. They are not
+    // held back until the board settles, the settle timing depends on the carriers, which
+    // do not move the same way in every run
+    fn take_inputs(&mut self, tick: u64) -> Vec<BoardEvent> {
+        let mut inputs = Vec::new();
+        if let Some(events) = self.replay.as_mut() {
+            while let Some(&(at, event)) = events.front() {
+                match event {
+                    BoardEvent::Removal(_) | BoardEvent::Page(_) => break,
+                    _ if at <= tick => {
+                        inputs.push(event);
+                        events.pop_front();
+                    }
+                    _ => break,
+                }
+            }
+        }
+        inputs
+    }
+
+    // Changes the board makes on its own are taken at their place in the tick
+    fn take_board_change(
+        &mut self,
+        tick: u64,
+        change: fn(BoardEvent) -> Option<usize>,
+    ) -> Option<usize> {
+        let events = self.replay.as_mut()?;
+        let &(at, event) = events.front()?;
+        let value = change(event).filter(|_| at <= tick)?;
+        events.pop_front();
+        Some(value)
+    }
+
+    fn take_removal(&mut self, tick: u64) -> Option<usize> {
+        self.take_board_change(tick, |event| match event {
+            BoardEvent::Removal(position) => Some(position),
+            _ => None,
+        })
+    }
+
+    fn take_page(&mut self, tick: u64) -> Option<usize> {
+        self.take_board_change(tick, |event| match event {
+            BoardEvent::Page(page) => Some(page),
+            _ => None,
+        })
+    }
+}
+
+// Seed used when none is given on the command line, printed so the run can be reproduced
+// Either a full RFC 3339 date and time, or "HH:MM[:SS]" today
+fn parse_start_time(text: &str) -> Option<DateTime<Local>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Some(time.with_timezone(&Local));
+    }
+    let time = NaiveTime::parse_from_str(text, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(text, "%H:%M"))
+        .ok()?;
+    time_of_day(time.num_seconds_from_midnight())
+}
+
+// Seeded runs start at a time of day picked by the seed, so the board does not depend on the hour
+fn seeded_start_time(seed: u64) -> Option<DateTime<Local>> {
+    time_of_day((seed % SECONDS_PER_DAY) as u32)
+}
+
+fn time_of_day(seconds: u32) -> Option<DateTime<Local>> {
+    Local::now()
+        .with_hour(seconds / 3600)?
+        .with_minute(seconds / 60 % 60)?
+        .with_second(seconds % 60)?
+        .with_nanosecond(0)
+}
+
+fn random_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64)
+}
+
+fn get_random_station_name<'a>(station_names: &'a [String], rng: &mut ChaChaRng) -> &'a str {
+    &station_names[rng.gen_range(0, station_names.len())]
+}
+
+fn is_facing_left(angle: f64) -> bool {
+    (angle > (std::f64::consts::PI / 2.0)) && (angle < ((3.0 / 2.0) * std::f64::consts::PI))
+}
+
+// Grows the swarm while there is work pending and shrinks it once the board settles
+struct AutoSwarm {
+    min: u8,
+    max: u8,
+    spawn_interval: u64,
+    retire_interval: u64,
+    ticks_left: u64,
+}
+
+impl AutoSwarm {
+    fn from_args() -> Option<AutoSwarm> {
+        if !arg_flag("--auto-swarm") {
+            return None;
+        }
+        let min = arg_number("--min-carriers", AUTO_SWARM_MIN_CARRIERS).max(MIN_CARRIERS);
+        let max = arg_number("--max-carriers", AUTO_SWARM_MAX_CARRIERS)
+            .min(MAX_CARRIERS)
+            .max(min);
+        Some(AutoSwarm {
+            min,
+            max,
+            spawn_interval: arg_number("--spawn-interval", AUTO_SWARM_SPAWN_INTERVAL).max(1),
+            retire_interval: arg_number("--retire-interval", AUTO_SWARM_RETIRE_INTERVAL).max(1),
+            ticks_left: 0,
+        })
+    }
+
+    // Returns how the swarm size should change: 1 to spawn, -1 to retire a carrier.
+    // Shrinking waits for a carrier the swarm reports idle, a busy one holds its slots
+    fn tick(&mut self, pending_slots: usize, carriers: u8, idle_carriers: usize) -> i8 {
+        self.ticks_left = self.ticks_left.saturating_sub(1);
+        if self.ticks_left > 0 {
+            return 0;
+        }
+
+        let wanted = pending_slots
+            .div_ceil(AUTO_SWARM_SLOTS_PER_CARRIER)
+            .clamp(self.min as usize, self.max as usize) as u8;
+        if carriers < wanted {
+            self.ticks_left = self.spawn_interval;
+            1
+        } else if carriers > wanted && idle_carriers > 0 {
+            self.ticks_left = self.retire_interval;
+            -1
+        } else {
+            0
+        }
+    }
+}
+
+// Carrier taken out of the swarm, flying away to the nearest pit
+struct RetiredCarrier {
+    position: [f64; 2],
+    target: [f64; 2],
+    speed: f64,
+    phase: f64,
+    kind: usize,
+}
+
+impl RetiredCarrier {
+    // Returns true once the pit is reached
+    fn tick(&mut self, dt: f64) -> bool {
+        let dx = self.target[0] - self.position[0];
+        let dy = self.target[1] - self.position[1];
+        let distance = (dx * dx + dy * dy).sqrt();
+        if distance <= self.speed {
+            return true;
+        }
+        self.position[0] += dx / distance * self.speed;
+        self.position[1] += dy / distance * self.speed;
+        self.phase += dt * (CARRIER_ANIM_FRAME_RATE + self.speed * CARRIER_ANIM_SPEED_FACTOR);
+        false
+    }
+
+    fn frame(&self, frames: &[TextureId]) -> TextureId {
+        frames[self.phase as usize % frames.len()]
+    }
+
+    fn facing(&self) -> f64 {
+        if self.target[0] < self.position[0] {
+            -1.0
+        } else {
+            1.0
+        }
+    }
+}
+
+// Decides how many simulation steps are run in each update
+struct Simulation {
+    paused: bool,
+    step_requested: bool,
+    time_scale: f64,
+    pending_steps: f64,
+}
+
+impl Simulation {
+    fn new() -> Simulation {
+        Simulation {
+            paused: false,
+            step_requested: false,
+            time_scale: 1.0,
+            pending_steps: 0.0,
+        }
+    }
+
+    fn steps(&mut self) -> u32 {
+        if self.paused {
+            let step = self.step_requested;
+            self.step_requested = false;
+            return step as u32;
+        }
+
+        // Slow motion runs a step every few updates, fast forward several steps per update
+        self.pending_steps += self.time_scale;
+        let steps = self.pending_steps.floor();
+        self.pending_steps -= steps;
+        steps as u32
+    }
+
+    fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.pending_steps = 0.0;
+        println!("{}", if self.paused { "Paused" } else { "Resumed" });
+    }
+
+    // Only meaningful when paused
+    fn step(&mut self) {
+        if self.paused {
+            self.step_requested = true;
+        }
+    }
+
+    fn scale_time(&mut self, factor: f64) {
+        self.time_scale = (self.time_scale * factor).clamp(TIME_SCALE_MIN, TIME_SCALE_MAX);
+        println!("Time scale {}x", self.time_scale);
+    }
+}
+
+// What changed the board, the ticking clock in the title row is not a transition at all
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum TransitionCause {
+    Departure,
+    Removal,
+    Cancellation,
+    Page,
+    Scroll,
+    Layout,
+}
+
+impl std::fmt::Display for TransitionCause {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TransitionCause::Departure => write!(f, "departure"),
+            TransitionCause::Removal => write!(f, "removal"),
+            TransitionCause::Cancellation => write!(f, "cancellation"),
+            TransitionCause::Page => write!(f, "page"),
+            TransitionCause::Scroll => write!(f, "scroll"),
+            TransitionCause::Layout => write!(f, "layout"),
+        }
+    }
+}
+
+// Statistics of a single change of the board, from the update it was caused in
+// until the swarm reports completion
+#[derive(Default)]
+struct Transition {
+    causes: Vec<TransitionCause>,
+    // Still in progress when the program was closed
+    interrupted: bool,
+    ticks: u64,
+    distance: f64,
+    pickups: u32,
+    drops: u32,
+    carrier_ticks: u64,
+    idle_carrier_ticks: u64,
+}
+
+impl Transition {
+    fn idle_ratio(&self) -> f64 {
+        if self.carrier_ticks == 0 {
+            0.0
+        } else {
+            self.idle_carrier_ticks as f64 / self.carrier_ticks as f64
+        }
+    }
+
+    fn cause_names(&self) -> Vec<String> {
+        self.causes.iter().map(|cause| cause.to_string()).collect()
+    }
+
+    fn to_csv(&self, index: usize) -> String {
+        format!(
+            "{},{},{},{:.1},{},{},{:.3},{}",
+            index,
+            self.cause_names().join("+"),
+            self.ticks,
+            self.distance,
+            self.pickups,
+            self.drops,
+            self.idle_ratio(),
+            self.interrupted
+        )
+    }
+
+    fn to_json(&self, index: usize) -> String {
+        let causes: Vec<String> = self
+            .cause_names()
+            .iter()
+            .map(|name| format!("\"{}\"", name))
+            .collect();
+        format!(
+            "{{\"transition\":{},\"causes\":[{}],\"ticks\":{},\"distance\":{:.1},\"pickups\":{},\"drops\":{},\"idle_ratio\":{:.3},\"interrupted\":{}}}",
+            index,
+            causes.join(","),
+            self.ticks,
+            self.distance,
+            self.pickups,
+            self.drops,
+            self.idle_ratio(),
+            self.interrupted
+        )
+    }
+}
+
+// Collects the transitions, optionally streaming each of them into a CSV or JSON lines file
+struct SwarmMetrics {
+    // Causes noted since the last step, they start the next transition
+    causes: Vec<TransitionCause>,
+    current: Option<Transition>,
+    completed: Vec<Transition>,
+    stream: Option<(File, bool)>,
+}
+
+impl SwarmMetrics {
+    // Files ending with '.csv' get CSV, anything else JSON lines
+    fn from_args() -> SwarmMetrics {
+        let stream = arg_value("--metrics").and_then(|path| {
+            let csv = path.ends_with(".csv");
+            match File::create(&path) {
+                Ok(mut file) => {
+                    if csv {
+                        let _ = writeln!(file, "{}", METRICS_CSV_HEADER);
+                    }
+                    Some((file, csv))
+                }
+                Err(e) => {
+                    println!("Unable to create metrics file '{}': {}", path, e);
+                    None
+                }
+            }
+        });
+        SwarmMetrics {
+            causes: Vec::new(),
+            current: None,
+            completed: Vec::new(),
+            stream,
+        }
+    }
+
+    // Board change joining the current transition, or starting a new one
+    fn cause(&mut self, cause: TransitionCause) {
+        let causes = match self.current.as_mut() {
+            Some(transition) => &mut transition.causes,
+            None => &mut self.causes,
+        };
+        if !causes.contains(&cause) {
+            causes.push(cause);
+        }
+    }
+
+    fn tick(&mut self, carriers: &[CarrierState], idle: bool) {
+        if self.current.is_none() && !self.causes.is_empty() {
+            self.current = Some(Transition {
+                causes: std::mem::take(&mut self.causes),
+                ..Transition::default()
+            });
+        }
+
+        if let Some(transition) = self.current.as_mut() {
+            for state in carriers {
+                let distance = state.speed();
+                transition.distance += distance;
+                transition.carrier_ticks += 1;
+                if state.swarm_state == swarm_it::State::IDLE {
+                    transition.idle_carrier_ticks += 1;
+                }
+                if state.picked_up() {
+                    transition.pickups += 1;
+                }
+                if state.dropped().is_some() {
+                    transition.drops += 1;
+                }
+            }
+            transition.ticks += 1;
+        }
+        if idle {
+            if let Some(transition) = self.current.take() {
+                self.complete(transition);
+            }
+        }
+    }
+
+    fn complete(&mut self, transition: Transition) {
+        let index = self.completed.len();
+        if let Some((file, csv)) = self.stream.as_mut() {
+            let line = if *csv {
+                transition.to_csv(index)
+            } else {
+                transition.to_json(index)
+            };
+            if let Err(e) = writeln!(file, "{}", line) {
+                println!("Unable to write metrics: {}", e);
+                self.stream = None;
+            }
+        }
+        self.completed.push(transition);
+    }
+
+    // Transition still in progress on exit is recorded as well
+    fn finish(&mut self) {
+        if let Some(mut transition) = self.current.take() {
+            transition.interrupted = true;
+            self.complete(transition);
+        }
+    }
+
+    fn print_summary(&self) {
+        let count = self.completed.len();
+        let interrupted = self.completed.iter().filter(|t| t.interrupted).count();
+        println!(
+            "Swarm metrics: {} transitions completed, {} interrupted",
+            count - interrupted,
+            interrupted
+        );
+        if count == 0 {
+            return;
+        }
+        let causes: Vec<String> = [
+            TransitionCause::Departure,
+            TransitionCause::Removal,
+            TransitionCause::Cancellation,
+            TransitionCause::Page,
+            TransitionCause::Scroll,
+            TransitionCause::Layout,
+        ]
+        .iter()
+        .map(|cause| {
+            let count = self
+                .completed
+                .iter()
+                .filter(|t| t.causes.contains(cause))
+                .count();
+            format!("{} {}", cause, count)
+        })
+        .collect();
+        println!("  causes: {}", causes.join(", "));
+        let ticks: Vec<u64> = self.completed.iter().map(|t| t.ticks).collect();
+        let carrier_ticks: u64 = self.completed.iter().map(|t| t.carrier_ticks).sum();
+        let idle_carrier_ticks: u64 = self.completed.iter().map(|t| t.idle_carrier_ticks).sum();
+        println!(
+            "  ticks per transition: min {}, max {}, mean {:.1}",
+            ticks.iter().min().unwrap(),
+            ticks.iter().max().unwrap(),
+            ticks.iter().sum::<u64>() as f64 / count as f64
+        );
+        println!(
+            "  distance flown: {:.1}",
+            self.completed.iter().map(|t| t.distance).sum::<f64>()
+        );
+        println!(
+            "  pickups: {}, drops: {}",
+            self.completed.iter().map(|t| t.pickups).sum::<u32>(),
+            self.completed.iter().map(|t| t.drops).sum::<u32>()
+        );
+        if carrier_ticks > 0 {
+            println!(
+                "  idle ratio: {:.3}",
+                idle_carrier_ticks as f64 / carrier_ticks as f64
+            );
+        }
+    }
+}
+
+// Only a carrier the swarm reports idle is retired. Any other one may hold a payload or
+// a reserved slot, so nothing is retired until one of the carriers finishes its job
+fn retire_carrier(
+    game: &mut MyGameType,
+    carrier_states: &mut CarrierStates,
+    types: &[CarrierType],
+    pits: &[[f64; 2]],
+) -> Option<RetiredCarrier> {
+    let index = game
+        .get_carriers()
+        .iter()
+        .rposition(|c| c.get_state() == swarm_it::State::IDLE)?;
+    let pos = carrier_states.states[index].position;
+    let distance = |pit: &&[f64; 2]| (pit[0] - pos[0]).powi(2) + (pit[1] - pos[1]).powi(2);
+    let target = pits
+        .iter()
+        .min_by(|a, b| distance(a).partial_cmp(&distance(b)).unwrap())?;
+
+    let state = carrier_states.remove(index)?;
+    game.get_carriers_mut().remove(index);
+    game.slot_data_changed();
+    Some(RetiredCarrier {
+        position: pos,
+        target: *target,
+        speed: types[state.kind].max_speed * RETIRED_CARRIER_SPEED_FACTOR,
+        phase: state.phase,
+        kind: state.kind,
+    })
+}
+
+struct CarrierType {
+    name: String,
+    acceleration: f64,
+    max_speed: f64,
+    spawn_weight: u32,
+    scale: f64,
+    sprites: Option<String>,
+    tint: [f32; 4],
+    frames: Vec<TextureId>,
 }
 
-fn fill_time_commas(game: &mut MyGameType) {
-    let slots = game.get_slots_mut();
-    for i in 0..TILES_PER_COLUMN {
-        slots[(row_end_index(i) - 3) as usize].set_payloads(char_to_payload(':'));
+impl Default for CarrierType {
+    fn default() -> CarrierType {
+        CarrierType {
+            name: "default".to_string(),
+            acceleration: CARRIER_ACCELERATION,
+            max_speed: CARRIER_MAX_SPEED,
+            spawn_weight: 1,
+            scale: 1.0,
+            sprites: None,
+            tint: [1.0; 4],
+            frames: CARRIER_FRAMES.to_vec(),
+        }
     }
 }
 
-fn move_all_rows_up(slots: &mut Vec<swarm_it::Slot<TextureId>>) {
-    for y in 0..TILES_PER_COLUMN - 1 {
-        for x in 0..TILES_PER_ROW {
-            let payloads = slots[slot_index(x, y + 1)].get_payloads();
-            slots[slot_index(x, y)].set_target_payload(payloads[0]);
+impl CarrierType {
+    // Line of the carrier types file the type would be read from
+    fn definition(&self) -> Option<String> {
+        let sprites = self.sprites.as_ref()?;
+        let tint = if self.tint == [1.0; 4] {
+            String::new()
+        } else {
+            format!(
+                " {} {} {} {}",
+                self.tint[0], self.tint[1], self.tint[2], self.tint[3]
+            )
+        };
+        Some(format!(
+            "{} {} {} {} {} {}{}",
+            self.name,
+            self.acceleration,
+            self.max_speed,
+            self.spawn_weight,
+            self.scale,
+            sprites,
+            tint
+        ))
+    }
+
+    fn parse(line: &str) -> Option<CarrierType> {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() != 6 && parts.len() != 10 {
+            return None;
         }
+        let tint = if parts.len() == 10 {
+            let numbers: Vec<f64> = parts[6..].iter().filter_map(|v| v.parse().ok()).collect();
+            if numbers.len() != 4 {
+                return None;
+            }
+            to_color(&numbers)
+        } else {
+            [1.0; 4]
+        };
+        Some(CarrierType {
+            name: parts[0].to_string(),
+            acceleration: parts[1].parse().ok()?,
+            max_speed: parts[2].parse().ok()?,
+            spawn_weight: parts[3].parse().ok()?,
+            scale: parts[4].parse().ok()?,
+            sprites: Some(parts[5].trim_end_matches('/').to_string()),
+            tint,
+            frames: CARRIER_FRAMES.to_vec(),
+        })
     }
 }
 
-fn put_next_train_in_last_row(game: &mut MyGameType, time: DateTime<Utc>, new_station: &str) {
-    fill_row_with_text(game, TILES_PER_COLUMN - 1, new_station, true);
-    fill_row_departure_time(game, TILES_PER_COLUMN - 1, time, true);
-
-    // Take special care about the HH:MM separator
-    game.get_slots_mut()[slot_index(TILES_PER_ROW - 3, TILES_PER_COLUMN - 1)]
-        .set_payloads(char_to_payload(':'));
+// Without the file every carrier is of the built-in type
+fn load_carrier_types(file: &str) -> Vec<CarrierType> {
+    let buffer = match File::open(file) {
+        Ok(file) => BufReader::new(file),
+        Err(_) => return vec![CarrierType::default()],
+    };
+    println!("Loading carrier types from '{}'", file);
+    parse_carrier_types(buffer.lines().map_while(|line| line.ok()).collect())
 }
 
-fn train_departure(
-    game: &mut MyGameType,
-    last_time: DateTime<Utc>,
-    new_station: &str,
-) -> DateTime<Utc> {
-    let next_time = increase_departure_time(last_time);
-    move_all_rows_up(game.get_slots_mut());
-    put_next_train_in_last_row(game, next_time, new_station);
-    game.slot_data_changed();
-    next_time
+fn parse_carrier_types(lines: Vec<String>) -> Vec<CarrierType> {
+    let types: Vec<CarrierType> = lines
+        .into_iter()
+        .filter(|line| !(line.trim().is_empty() || line.starts_with('#')))
+        .filter_map(|line| {
+            let carrier_type = CarrierType::parse(&line);
+            if carrier_type.is_none() {
+                println!("Invalid carrier type '{}'", line);
+            }
+            carrier_type
+        })
+        .filter(|carrier_type| carrier_type.spawn_weight > 0)
+        .collect();
+    if types.is_empty() {
+        vec![CarrierType::default()]
+    } else {
+        types
+    }
 }
 
-fn load_station_names(list: &mut Vec<String>, file: &str) -> Result<()> {
-    println!("Loading stations from '{}'", file);
-    let file = File::open(file)?;
-    let buffer = BufReader::new(file);
-    buffer.lines().for_each(|line| list.push(line.unwrap()));
-
-    println!("{} station(s) loaded", list.len());
-    Ok(())
+// Frames are 'frame-1.png', 'frame-2.png'... in the sprite directory under 'images/'
+fn carrier_sprite_path(dir: &str, frame: usize) -> String {
+    format!("{}{}/frame-{}.png", IMAGES_DIR, dir, frame + 1)
 }
 
-fn get_random_station_name(station_names: &[String]) -> &str {
-    let mut rng = rand::thread_rng();
-    &station_names[rng.gen_range(0, station_names.len())]
+fn load_carrier_sprites(
+    depot: &mut HashMap<TextureId, G2dTexture>,
+    context: &mut G2dTextureContext,
+    theme: &Theme,
+    types: &mut [CarrierType],
+) {
+    for (kind, carrier_type) in types.iter_mut().enumerate() {
+        let dir = match &carrier_type.sprites {
+            Some(dir) => dir,
+            None => continue,
+        };
+        let mut frames = Vec::new();
+        for frame in 0..CARRIER_SPRITE_MAX_FRAMES {
+            let path = theme.resolve(&carrier_sprite_path(dir, frame));
+            if !std::path::Path::new(&path).exists() {
+                break;
+            }
+            match Texture::from_path(context, &path, Flip::None, &TextureSettings::new()) {
+                Ok(texture) => {
+                    depot.insert(TextureId::CarrierSprite(kind, frame), texture);
+                    frames.push(TextureId::CarrierSprite(kind, frame));
+                }
+                Err(e) => println!("Unable to load texture '{}': {}", path, e),
+            }
+        }
+
+        if frames.is_empty() {
+            println!(
+                "No sprites for carrier type '{}', using the default ones",
+                carrier_type.name
+            );
+        } else {
+            carrier_type.frames = frames;
+        }
+    }
 }
 
-fn is_facing_left(carrier: &swarm_it::Carrier<TextureId>) -> bool {
-    let angle = carrier.get_angle();
-    (angle > (std::f64::consts::PI / 2.0)) && (angle < ((3.0 / 2.0) * std::f64::consts::PI))
+fn pick_carrier_type(types: &[CarrierType], rng: &mut ChaChaRng) -> usize {
+    let total: u32 = types.iter().map(|t| t.spawn_weight).sum();
+    let mut pick = rng.gen_range(0, total);
+    for (kind, carrier_type) in types.iter().enumerate() {
+        if pick < carrier_type.spawn_weight {
+            return kind;
+        }
+        pick -= carrier_type.spawn_weight;
+    }
+    0
 }
 
-fn add_one_carrier(game: &mut MyGameType, x: f64, y: f64) {
+fn add_one_carrier(
+    game: &mut MyGameType,
+    carrier_states: &mut CarrierStates,
+    types: &[CarrierType],
+    x: f64,
+    y: f64,
+    rng: &mut ChaChaRng,
+) {
+    let kind = pick_carrier_type(types, rng);
     let index = game.add_carrier(Carrier::new(x, y));
     let carrier = &mut game.get_carriers_mut()[index];
-    carrier.set_acceleration(CARRIER_ACCELERATION);
-    carrier.set_max_speed(CARRIER_MAX_SPEED);
+    carrier.set_acceleration(types[kind].acceleration);
+    carrier.set_max_speed(types[kind].max_speed);
+    carrier_states.add(kind, [x, y]);
+}
+
+// New carriers enter the board at one of the spawners of the layout
+fn spawn_carrier(
+    game: &mut MyGameType,
+    carrier_states: &mut CarrierStates,
+    types: &[CarrierType],
+    spawners: &[[f64; 2]],
+    rng: &mut ChaChaRng,
+) {
+    let spawner = spawners[rng.gen_range(0, spawners.len())];
+    add_one_carrier(game, carrier_states, types, spawner[0], spawner[1], rng);
 }
 
 fn main() -> Result<()> {
     let opengl = OpenGL::V3_2;
+    let windowed = arg_flag("--windowed");
     let mut window: PistonWindow = WindowSettings::new(
         "Timetable: Demo of the Swarm library by mgr. inż. Rafał",
-        SCREEN_SIZE_NATIVE,
+        if windowed {
+            WINDOW_SIZE_DEFAULT
+        } else {
+            SCREEN_SIZE_NATIVE
+        },
     )
     .exit_on_esc(true)
-    .fullscreen(true)
+    .fullscreen(!windowed)
+    .resizable(windowed)
     .opengl(opengl)
     .build()
     .unwrap();
 
+    // Replay restores the simulation options, so the log is read before any of them
+    let mut event_log = EventLog::from_args()?;
+    let mode = BoardMode::from_args();
+    let mut display_mode = DisplayMode::from_args();
+    let mut split_flap = SplitFlap::new();
+    let scale_mode = ScaleMode::from_args();
+    let window_size = window.size();
+    let mut view = ViewTransform::new([window_size.width, window_size.height], scale_mode);
     let mut allow_next_departure = false;
-    let mut game = swarm_it::Swarm::<TextureId>::new();
-
-    let carrier_frames: [TextureId; 8] = [
-        TextureId::Carrier01,
-        TextureId::Carrier02,
-        TextureId::Carrier03,
-        TextureId::Carrier04,
-        TextureId::Carrier05,
-        TextureId::Carrier06,
-        TextureId::Carrier07,
-        TextureId::Carrier08,
-    ];
-    let mut carrier_anim_cycle = carrier_frames.iter().cycle();
-    let mut carrier_anim_counter = 0;
-    let mut carrier_anim_texture = carrier_anim_cycle.next().unwrap();
+    let mut game = swarm_it::Swarm::<Glyph>::new();
+
+    let given_seed = arg_value("--seed").and_then(|seed| seed.parse().ok());
+    let given_start = arg_value("--start").and_then(|time| {
+        let start = parse_start_time(&time);
+        if start.is_none() {
+            println!("Invalid start time '{}'", time);
+        }
+        start
+    });
+    let seed = event_log.seed.or(given_seed).unwrap_or_else(random_seed);
+    let start_time = event_log
+        .start_time
+        .or(given_start)
+        .or_else(|| given_seed.and_then(seeded_start_time))
+        .unwrap_or_else(Local::now);
+    event_log.start(
+        seed,
+        start_time,
+        given_seed.is_some() || given_start.is_some(),
+    );
+    let mut tick: u64 = 0;
+    let mut input_events: Vec<BoardEvent> = Vec::new();
+    println!("Random seed: {}", seed);
+    // A named generator, so a logged seed gives the same draws whatever rand version is built
+    let mut rng = ChaChaRng::seed_from_u64(seed);
+    // Carriers and visual effects draw from their own streams, so adding carriers or toggling
+    // the effects does not change the schedule
+    let mut carrier_states = CarrierStates::new(ChaChaRng::from_rng(&mut rng).unwrap());
+    let effects_rng = ChaChaRng::from_rng(&mut rng).unwrap();
+    let mut carrier_rng = ChaChaRng::from_rng(&mut rng).unwrap();
     let mut station_names = Vec::new();
 
     let mut ctx = window.create_texture_context();
     let mut texture_depot = HashMap::new();
-    load_textures(&mut texture_depot, &mut ctx);
-    load_station_names(&mut station_names, "stations/uk.txt")?;
-    load_layout(&mut game, 2)?;
-    fill_with_station_names(&mut game, &station_names);
-    fill_time_commas(&mut game);
-    let mut last_time = fill_departure_times(&mut game);
+    let theme = Theme::from_args()?;
+    if let Err(e) = load_textures(&mut texture_depot, &mut ctx, &theme) {
+        println!("{}", e);
+    }
+    let mut carrier_types = match event_log.carrier_types.take() {
+        Some(definitions) => parse_carrier_types(definitions),
+        None => load_carrier_types(CARRIER_TYPES_FILE),
+    };
+    event_log.record_carrier_types(&carrier_types);
+    load_carrier_sprites(&mut texture_depot, &mut ctx, &theme, &mut carrier_types);
+    load_station_names(&mut station_names, STATIONS_FILE)?;
+    let layout = load_layout(&mut game, LAYOUT_ID)?;
+    let board_slots = game.get_slots().len();
+    let mut layout_settings = layout.settings;
+    let mut background_files = background_paths(&layout_settings, &theme);
+    let background_interval = arg_number("--background-interval", BACKGROUND_INTERVAL);
+    let mut backgrounds = Backgrounds::load(&background_files, background_interval, &mut ctx);
+    let mut watched_dirs = vec![
+        IMAGES_DIR.to_string(),
+        LAYOUTS_DIR.to_string(),
+        parent_dir(STATIONS_FILE),
+    ];
+    watched_dirs.extend(theme.dir.iter().cloned());
+    watched_dirs.extend(arg_value("--backgrounds"));
+    watched_dirs.extend(background_files.iter().map(|path| parent_dir(path)));
+    let mut file_watcher = FileWatcher::new(&watched_dirs);
+    let mut pending_layout: Option<Layout> = None;
+    fill_title(&mut game, mode, start_time, false);
+    fill_header(&mut game, mode, false);
+    let mut trains = create_trains(mode, &station_names, start_time, &mut rng);
+    let mut cancellations: Vec<Cancellation> = Vec::new();
+    let cancelled_display_ticks =
+        arg_number("--cancelled-time", CANCELLED_DISPLAY_TIME) * UPDATES_PER_SECOND;
+    let mut page = 0;
+    let page_interval = arg_number("--page-interval", PAGE_INTERVAL).max(1) * UPDATES_PER_SECOND;
+    let mut page_ticks_left = page_interval;
+    fill_page(&mut game, mode, &trains, &cancellations, page, false);
+    let mut calling_points = if mode.has_calling_points() {
+        let calling_points = CallingPoints::new(&trains[0].calling_points);
+        fill_calling_points(&mut game, mode, &calling_points, false);
+        Some(calling_points)
+    } else {
+        None
+    };
     game.slot_data_changed();
     add_one_carrier(
         &mut game,
+        &mut carrier_states,
+        &carrier_types,
         f64::from(SCREEN_SIZE_NATIVE[0] / 2),
         f64::from(SCREEN_SIZE_NATIVE[1] / 2),
+        &mut carrier_rng,
     );
     let mut current_carriers_count = game.get_carriers().len() as u8;
+    let mut carriers_to_retire = 0;
+    let mut retired_carriers: Vec<RetiredCarrier> = Vec::new();
+    let mut auto_swarm = AutoSwarm::from_args();
     let mut draw_carriers = true;
+    let mut clock_second = start_time.second();
+
+    let mut debug_overlay = DebugOverlay::new();
+    let mut effects = Effects::new(effects_rng);
+    let mut metrics = SwarmMetrics::from_args();
+    let mut simulation = Simulation::new();
 
-    game.add_slot(make_slot_pit!(-75.0, f64::from(SCREEN_SIZE_NATIVE[1] / 2)));
-    game.add_slot(make_slot_pit!(
-        f64::from(SCREEN_SIZE_NATIVE[0] + 75),
-        f64::from(SCREEN_SIZE_NATIVE[1] / 2)
-    ));
-    game.add_slot(make_slot_spawner!(
-        -75.0,
-        f64::from(SCREEN_SIZE_NATIVE[1] / 2)
-    ));
-    game.add_slot(make_slot_spawner!(
-        f64::from(SCREEN_SIZE_NATIVE[0] + 75),
-        f64::from(SCREEN_SIZE_NATIVE[1] / 2)
-    ));
-
-    window.set_ups(60);
+    add_pits_and_spawners(&mut game, &layout_settings);
+
+    window.set_ups(UPDATES_PER_SECOND);
 
     while let Some(e) = window.next() {
-        e.update(|_| {
-            if game.tick() {
-                allow_next_departure = true
+        e.resize(|w, h| view = ViewTransform::new([w, h], scale_mode));
+
+        e.update(|args| {
+            backgrounds.tick();
+
+            let changed_paths = file_watcher.tick();
+            for path in &changed_paths {
+                if path == STATIONS_FILE {
+                    let mut reloaded = Vec::new();
+                    match load_station_names(&mut reloaded, STATIONS_FILE) {
+                        Ok(()) if !reloaded.is_empty() => station_names = reloaded,
+                        _ => println!("Keeping the previous station list"),
+                    }
+                } else if *path == layout_path(LAYOUT_ID) {
+                    if let Ok(layout) = read_layout(path) {
+                        pending_layout = Some(layout);
+                    }
+                } else {
+                    reload_texture(
+                        &mut texture_depot,
+                        &mut ctx,
+                        &theme,
+                        &mut carrier_types,
+                        path,
+                    );
+                }
             }
-        });
 
-        e.release(|args| {
-            if let piston_window::Button::Keyboard(k) = args {
-                match k {
-                    piston_window::Key::Space => {
-                        if allow_next_departure {
-                            last_time = train_departure(
+            if let Some(settings) =
+                apply_pending_layout(&mut game, &mut pending_layout, board_slots)
+            {
+                metrics.cause(TransitionCause::Layout);
+                layout_settings = settings;
+            }
+
+            // The playlist may have gained an image, or the layout points to another one
+            let paths = background_paths(&layout_settings, &theme);
+            if paths != background_files
+                || changed_paths
+                    .iter()
+                    .any(|path| background_files.contains(path))
+            {
+                background_files = paths;
+                backgrounds = Backgrounds::load(&background_files, background_interval, &mut ctx);
+                background_files
+                    .iter()
+                    .for_each(|p| file_watcher.watch(&parent_dir(p)));
+            }
+
+            // Simulation runs at its own pace, the files and backgrounds follow the wall clock
+            debug_overlay.updates.tick();
+            for _ in 0..simulation.steps() {
+                let replaying = event_log.replaying();
+                let mut events = if replaying {
+                    event_log.take_inputs(tick)
+                } else {
+                    std::mem::take(&mut input_events)
+                };
+                // Recorded like a key press, so the replay brings it from the log
+                if !replaying
+                    && allow_next_departure
+                    && train_arrived(mode, &trains, event_log.board_time(start_time, tick))
+                {
+                    events.insert(0, BoardEvent::Departure);
+                }
+                // Replayed events were checked when recorded, so they are never dropped
+                for event in events {
+                    let applied = match event {
+                        BoardEvent::Departure if allow_next_departure || replaying => {
+                            compact_cancellations(&mut cancellations, 0);
+                            train_departure(
                                 &mut game,
-                                last_time,
-                                get_random_station_name(&station_names),
+                                mode,
+                                &mut trains,
+                                page,
+                                &cancellations,
+                                &station_names,
+                                event_log.board_time(start_time, tick),
+                                &mut rng,
                             );
-                            allow_next_departure = false
+                            refresh_calling_points(
+                                &mut game,
+                                mode,
+                                &mut calling_points,
+                                top_train(mode, &trains, page),
+                            );
+                            metrics.cause(TransitionCause::Departure);
+                            allow_next_departure = false;
+                            true
+                        }
+                        BoardEvent::Cancellation if allow_next_departure || replaying => {
+                            let cancelled = cancel_random_train(
+                                &mut game,
+                                mode,
+                                &trains,
+                                page,
+                                &mut cancellations,
+                                cancelled_display_ticks,
+                                &mut rng,
+                            );
+                            if cancelled {
+                                metrics.cause(TransitionCause::Cancellation);
+                                allow_next_departure = false
+                            }
+                            cancelled
+                        }
+                        BoardEvent::ToggleCarriers => {
+                            draw_carriers = !draw_carriers;
+                            true
+                        }
+                        BoardEvent::ToggleDisplay => {
+                            display_mode = display_mode.toggled();
+                            true
                         }
+                        BoardEvent::AddCarrier
+                            if replaying || current_carriers_count < MAX_CARRIERS =>
+                        {
+                            current_carriers_count += 1;
+                            spawn_carrier(
+                                &mut game,
+                                &mut carrier_states,
+                                &carrier_types,
+                                &layout_settings.spawners(),
+                                &mut carrier_rng,
+                            );
+                            true
+                        }
+                        BoardEvent::RetireCarrier
+                            if replaying
+                                || current_carriers_count - carriers_to_retire > MIN_CARRIERS =>
+                        {
+                            carriers_to_retire += 1;
+                            true
+                        }
+                        _ => false,
+                    };
+                    if applied {
+                        event_log.record(tick, event);
+                    } else if replaying {
+                        println!(
+                            "Replay diverged: '{}' not applicable at tick {}",
+                            event, tick
+                        );
                     }
-                    piston_window::Key::H => draw_carriers = !draw_carriers,
-                    piston_window::Key::Plus | piston_window::Key::NumPadPlus => {
-                        if current_carriers_count < MAX_CARRIERS {
+                }
+
+                // Both keep running, only the displayed one holds the board back
+                let swarm_idle = game.tick();
+                split_flap.tick(game.get_slots());
+                let settled_rows: Vec<u32> = (0..TILES_PER_COLUMN)
+                    .filter(|row| match display_mode {
+                        DisplayMode::Swarm => rows_settled(game.get_slots(), &[*row]),
+                        DisplayMode::SplitFlap => {
+                            split_flap.rows_settled(game.get_slots(), &[*row])
+                        }
+                    })
+                    .collect();
+                let settled = |rows: Vec<u32>| rows.iter().all(|row| settled_rows.contains(row));
+                // Swarm is kept busy by the clock every second, which is not a change of the board
+                let idle = (swarm_idle && display_mode == DisplayMode::Swarm)
+                    || settled(mode.transition_rows());
+                carrier_states.tick(game.get_carriers(), args.dt);
+                metrics.tick(&carrier_states.states, idle);
+                effects.tick(&carrier_states.states, &theme);
+
+                if let Some(auto_swarm) = auto_swarm.as_mut() {
+                    let pending_slots = pending_slots(game.get_slots(), &mode.demand_rows()).len();
+                    let idle_carriers = game
+                        .get_carriers()
+                        .iter()
+                        .filter(|c| c.get_state() == swarm_it::State::IDLE)
+                        .count();
+                    match auto_swarm.tick(
+                        pending_slots,
+                        current_carriers_count - carriers_to_retire,
+                        idle_carriers,
+                    ) {
+                        1 if carriers_to_retire > 0 => carriers_to_retire -= 1,
+                        1 => {
+                            spawn_carrier(
+                                &mut game,
+                                &mut carrier_states,
+                                &carrier_types,
+                                &layout_settings.spawners(),
+                                &mut carrier_rng,
+                            );
                             current_carriers_count += 1;
-                            add_one_carrier(&mut game, f64::from(SCREEN_SIZE_NATIVE[0] / 2), -75.0);
                         }
+                        -1 => carriers_to_retire += 1,
+                        _ => {}
+                    }
+                }
+
+                if carriers_to_retire > 0 {
+                    if let Some(retired) = retire_carrier(
+                        &mut game,
+                        &mut carrier_states,
+                        &carrier_types,
+                        &layout_settings.pits(),
+                    ) {
+                        carriers_to_retire -= 1;
+                        current_carriers_count -= 1;
+                        retired_carriers.push(retired);
+                    }
+                }
+                retired_carriers.retain_mut(|c| !c.tick(args.dt));
+                // The ticking clock must not hold the departures back
+                if idle || settled(mode.body_rows()) {
+                    allow_next_departure = true
+                }
+
+                let now = event_log.board_time(start_time, tick);
+                if now.second() != clock_second {
+                    clock_second = now.second();
+                    fill_clock(&mut game, mode, now, true);
+                    game.slot_data_changed();
+                }
+
+                tick_cancellations(&settled_rows, mode, page, &mut cancellations);
+                let removal = if event_log.replaying() {
+                    event_log.take_removal(tick)
+                } else if allow_next_departure {
+                    cancellations
+                        .iter()
+                        .find(|c| c.ticks_left == 0)
+                        .map(|c| c.position)
+                } else {
+                    None
+                };
+                if let Some(position) = removal {
+                    event_log.record(tick, BoardEvent::Removal(position));
+                    compact_cancellations(&mut cancellations, position);
+                    train_removal(
+                        &mut game,
+                        mode,
+                        &mut trains,
+                        page,
+                        &cancellations,
+                        position,
+                        &station_names,
+                        now,
+                        &mut rng,
+                    );
+                    refresh_calling_points(
+                        &mut game,
+                        mode,
+                        &mut calling_points,
+                        top_train(mode, &trains, page),
+                    );
+                    metrics.cause(TransitionCause::Removal);
+                    allow_next_departure = false
+                }
+
+                // Cancellations pick from the shown page, so the replay turns it when recorded
+                let next_page = if event_log.replaying() {
+                    event_log.take_page(tick)
+                } else if allow_next_departure && page_count(mode, &trains) > 1 {
+                    page_ticks_left = page_ticks_left.saturating_sub(1);
+                    (page_ticks_left == 0).then(|| (page + 1) % page_count(mode, &trains))
+                } else {
+                    None
+                };
+                if let Some(next_page) = next_page {
+                    event_log.record(tick, BoardEvent::Page(next_page));
+                    page = next_page;
+                    page_ticks_left = page_interval;
+                    fill_page(&mut game, mode, &trains, &cancellations, page, true);
+                    refresh_calling_points(
+                        &mut game,
+                        mode,
+                        &mut calling_points,
+                        top_train(mode, &trains, page),
+                    );
+                    game.slot_data_changed();
+                    metrics.cause(TransitionCause::Page);
+                    allow_next_departure = false
+                }
+
+                // Scroll only while the board is settled, so departures are not starved
+                if let Some(calling_points) = calling_points.as_mut() {
+                    if allow_next_departure && calling_points.needs_scrolling() {
+                        calling_points.ticks_left = calling_points.ticks_left.saturating_sub(1);
+                        if calling_points.ticks_left == 0 {
+                            calling_points.scroll();
+                            fill_calling_points(&mut game, mode, calling_points, true);
+                            game.slot_data_changed();
+                            metrics.cause(TransitionCause::Scroll);
+                            allow_next_departure = false
+                        }
+                    }
+                }
+
+                tick += 1;
+            }
+        });
+
+        e.release(|args| {
+            if let piston_window::Button::Keyboard(k) = args {
+                // Board events are applied by the next simulation step, they come from
+                // the recording during a replay
+                let replaying = event_log.replaying();
+                match k {
+                    piston_window::Key::Space if !replaying => {
+                        input_events.push(BoardEvent::Departure)
+                    }
+                    piston_window::Key::C if !replaying => {
+                        input_events.push(BoardEvent::Cancellation)
+                    }
+                    piston_window::Key::H if !replaying => {
+                        input_events.push(BoardEvent::ToggleCarriers)
+                    }
+                    piston_window::Key::F if !replaying => {
+                        input_events.push(BoardEvent::ToggleDisplay)
+                    }
+                    piston_window::Key::D => debug_overlay.enabled = !debug_overlay.enabled,
+                    piston_window::Key::T => effects.toggle(),
+                    piston_window::Key::P => simulation.toggle_pause(),
+                    piston_window::Key::Period => simulation.step(),
+                    piston_window::Key::LeftBracket => simulation.scale_time(0.5),
+                    piston_window::Key::RightBracket => simulation.scale_time(2.0),
+                    piston_window::Key::Plus | piston_window::Key::NumPadPlus
+                        if !replaying && auto_swarm.is_none() =>
+                    {
+                        input_events.push(BoardEvent::AddCarrier)
+                    }
+                    piston_window::Key::Minus | piston_window::Key::NumPadMinus
+                        if !replaying && auto_swarm.is_none() =>
+                    {
+                        input_events.push(BoardEvent::RetireCarrier)
                     }
                     _ => {}
                 }
@@ -758,77 +4039,657 @@ fn main() -> Result<()> {
         });
 
         e.render(|_| {
-            window.draw_2d(&e, |ctx, g, _| {
+            window.draw_2d(&e, |window_ctx, g, _| {
                 // Clear
                 clear([0.0; 4], g);
+                let ctx = view.apply(window_ctx);
 
                 // Paint background
-                image(
-                    texture_depot.get(&TextureId::Background).unwrap(),
-                    ctx.transform,
-                    g,
-                );
+                match backgrounds.textures.get(backgrounds.current) {
+                    Some(texture) => image(texture, ctx.transform, g),
+                    None => rectangle(
+                        theme.background_color,
+                        [
+                            0.0,
+                            0.0,
+                            f64::from(SCREEN_SIZE_NATIVE[0]),
+                            f64::from(SCREEN_SIZE_NATIVE[1]),
+                        ],
+                        ctx.transform,
+                        g,
+                    ),
+                }
+                if let Some((texture, opacity)) = backgrounds.fading_in() {
+                    Image::new_color([1.0, 1.0, 1.0, opacity]).draw(
+                        texture,
+                        &ctx.draw_state,
+                        ctx.transform,
+                        g,
+                    );
+                }
 
                 // Paint slots
-                game.get_slots().iter().for_each(|&s| {
+                game.get_slots().iter().enumerate().for_each(|(i, &s)| {
                     let pos = s.get_position();
-                    let context = ctx.trans(pos.x, pos.y);
+                    let mut context = ctx.trans(pos.x, pos.y);
 
-                    let texture;
-                    if let Some(p) = s.get_payloads()[0] {
-                        texture = texture_depot.get(&p.cargo);
-                    } else {
-                        texture = texture_depot.get(&TextureId::TileBlank);
-                    }
+                    let shown = match display_mode {
+                        DisplayMode::Swarm => s.get_payloads()[0].map(|p| p.cargo),
+                        DisplayMode::SplitFlap => match split_flap.flapping_glyph(i) {
+                            Some((flapping, progress)) => {
+                                // Flap folds down to the middle of the tile and opens up again
+                                let fold = (1.0 - 2.0 * progress).abs().max(0.05);
+                                context = context
+                                    .trans(0.0, f64::from(TILE_HEIGHT) * (1.0 - fold) / 2.0)
+                                    .scale(1.0, fold);
+                                Some(flapping)
+                            }
+                            None => split_flap.shown_glyph(i),
+                        },
+                    };
+                    let glyph = shown.unwrap_or(Glyph {
+                        texture: TextureId::TileBlank,
+                        color: TileColor::Default,
+                    });
 
-                    Image::new_color([1.0, 1.0, 1.0, 0.85]).draw(
-                        texture.unwrap(),
-                        &ctx.draw_state,
-                        context.transform,
-                        g,
-                    );
+                    let texture = texture_depot.get(&glyph.texture);
+                    draw_texture(texture, glyph.color.rgba(&theme), &context, g);
                 });
 
-                // Paint carriers
-                carrier_anim_counter += 1;
-                if carrier_anim_counter == CARRIER_ANIM_SPEED {
-                    carrier_anim_counter = 0;
-                    carrier_anim_texture = carrier_anim_cycle.next().unwrap();
-                }
-                game.get_carriers().iter().for_each(|&c| {
-                    let pos = c.get_position();
-                    let mut context =
-                        ctx.trans(pos.x + CARRIER_ICON_X_OFFSET, pos.y + CARRIER_ICON_Y_OFFSET);
-                    if is_facing_left(&c) {
-                        context = context.flip_h().trans(-40.0, 0.0);
+                // Paint carriers, they keep working unseen in the split-flap mode
+                if display_mode == DisplayMode::Swarm {
+                    if effects.enabled {
+                        effects.draw(&carrier_states.states, &ctx, g);
                     }
 
-                    // Paint payload
-                    if let Some(p) = c.get_payload() {
-                        let texture = texture_depot.get(&p.cargo);
-                        let context = ctx.trans(pos.x, pos.y);
-                        Image::new_color([1.0, 1.0, 1.0, 0.85]).draw(
-                            texture.unwrap(),
-                            &context.draw_state,
-                            context.transform,
-                            g,
+                    game.get_carriers().iter().enumerate().for_each(|(i, &c)| {
+                        let pos = c.get_position();
+                        let context = ctx.trans(
+                            pos.x + theme.carrier_offset[0],
+                            pos.y + theme.carrier_offset[1],
                         );
-                    }
 
-                    // Paint carrier itself
+                        // Paint payload
+                        if let Some(p) = c.get_payload() {
+                            let texture = texture_depot.get(&p.cargo.texture);
+                            let context = ctx.trans(pos.x, pos.y);
+                            draw_texture(texture, p.cargo.color.rgba(&theme), &context, g);
+                        }
+
+                        // Paint carrier itself
+                        if let (true, Some(state)) = (draw_carriers, carrier_states.states.get(i)) {
+                            let carrier_type = &carrier_types[state.kind];
+                            let frame = state.frame(&carrier_type.frames);
+                            let texture = texture_depot.get(&frame);
+                            let size = texture.map_or(
+                                [f64::from(TILE_WIDTH), f64::from(TILE_HEIGHT)],
+                                |t| {
+                                    let (width, height) = t.get_size();
+                                    [f64::from(width), f64::from(height)]
+                                },
+                            );
+                            let context = context.scale(carrier_type.scale, carrier_type.scale);
+                            let context = state.orient(context, size);
+                            draw_texture(texture, carrier_type.tint, &context, g);
+                        }
+                    });
+
                     if draw_carriers {
-                        let texture = texture_depot.get(&carrier_anim_texture);
-                        Image::new_color([1.0, 1.0, 1.0, 1.0]).draw(
-                            texture.unwrap(),
-                            &ctx.draw_state,
-                            context.transform,
-                            g,
-                        );
+                        retired_carriers.iter().for_each(|c| {
+                            let carrier_type = &carrier_types[c.kind];
+                            let texture = texture_depot.get(&c.frame(&carrier_type.frames));
+                            let width = texture
+                                .map_or(f64::from(TILE_WIDTH), |t| f64::from(t.get_size().0));
+                            let context = ctx
+                                .trans(
+                                    c.position[0] + theme.carrier_offset[0],
+                                    c.position[1] + theme.carrier_offset[1],
+                                )
+                                .scale(carrier_type.scale, carrier_type.scale)
+                                .trans(width / 2.0, 0.0)
+                                .scale(c.facing(), 1.0)
+                                .trans(-width / 2.0, 0.0);
+                            draw_texture(texture, carrier_type.tint, &context, g);
+                        });
                     }
+                }
+
+                debug_overlay.frames.tick();
+                if debug_overlay.enabled {
+                    draw_debug_overlay(
+                        &debug_overlay,
+                        &game,
+                        &carrier_states.states,
+                        mode,
+                        &layout_settings,
+                        &texture_depot,
+                        &ctx,
+                        g,
+                    );
+                }
+
+                // Paint letterbox
+                view.letterbox_bars().iter().for_each(|bar| {
+                    rectangle([0.0, 0.0, 0.0, 1.0], *bar, window_ctx.transform, g);
                 });
             })
         });
     }
 
+    metrics.finish();
+    metrics.print_summary();
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glyph(c: char) -> Option<Payload<Glyph>> {
+        char_to_payload(c)
+    }
+
+    fn board_slots() -> Vec<Slot<Glyph>> {
+        (0..TILES_PER_ROW * TILES_PER_COLUMN)
+            .map(|_| Slot::new(0.0, 0.0, None, None, SlotKind::CLASSIC))
+            .collect()
+    }
+
+    fn fill_row(slots: &mut [Slot<Glyph>], row: u32, c: char) {
+        (0..TILES_PER_ROW).for_each(|x| slots[slot_index(x, row)].set_payloads(glyph(c)));
+    }
+
+    #[test]
+    fn short_calling_points_do_not_scroll() {
+        let calling_points = CallingPoints::new("CALLING AT: YORK");
+        assert!(!calling_points.needs_scrolling());
+        assert_eq!(calling_points.visible_text(), "CALLING AT: YORK");
+    }
+
+    #[test]
+    fn long_calling_points_scroll_around_with_a_gap() {
+        let text = "CALLING AT: DARLINGTON - YORK - DONCASTER";
+        let mut calling_points = CallingPoints::new(text);
+        assert!(calling_points.needs_scrolling());
+        assert_eq!(
+            calling_points.visible_text(),
+            text.chars()
+                .take(TILES_PER_ROW as usize)
+                .collect::<String>()
+        );
+
+        let length = text.len() + CALLING_POINTS_GAP.len();
+        (0..text.len()).for_each(|_| calling_points.scroll());
+        assert_eq!(
+            calling_points.visible_text(),
+            format!("{}{}", CALLING_POINTS_GAP, &text[..31])
+        );
+        (text.len()..length).for_each(|_| calling_points.scroll());
+        assert_eq!(calling_points.offset, 0);
+    }
+
+    #[test]
+    fn removed_row_is_replaced_by_the_rows_below() {
+        let mut slots = board_slots();
+        let train_rows = [2, 3, 4];
+        fill_row(&mut slots, 2, 'A');
+        fill_row(&mut slots, 3, 'B');
+        fill_row(&mut slots, 4, 'C');
+
+        remove_row_and_compact(&mut slots, &train_rows, 0);
+        assert!(slots[slot_index(0, 2)].get_payloads()[1] == glyph('B'));
+        assert!(slots[slot_index(35, 3)].get_payloads()[1] == glyph('C'));
+        // The last row is left for the caller to fill with the next train
+        assert!(slots[slot_index(0, 4)].get_payloads()[1] == glyph('C'));
+    }
+
+    #[test]
+    fn cancellations_follow_the_compacted_trains() {
+        let mut cancellations: Vec<Cancellation> = [0, 2, 5]
+            .iter()
+            .map(|position| Cancellation {
+                position: *position,
+                ticks_left: 1,
+            })
+            .collect();
+        compact_cancellations(&mut cancellations, 2);
+        let positions: Vec<usize> = cancellations.iter().map(|c| c.position).collect();
+        assert_eq!(positions, vec![0, 4]);
+    }
+
+    #[test]
+    fn cancellation_countdown_waits_for_the_row_to_settle() {
+        let mode = BoardMode::new(BoardKind::Departures);
+        let row = mode.train_rows()[1];
+        let mut cancellations = vec![Cancellation {
+            position: 1,
+            ticks_left: 10,
+        }];
+        tick_cancellations(&[], mode, 0, &mut cancellations);
+        assert_eq!(cancellations[0].ticks_left, 10);
+
+        tick_cancellations(&[row], mode, 0, &mut cancellations);
+        assert_eq!(cancellations[0].ticks_left, 9);
+
+        // Not on the current page, so not on the board at all
+        tick_cancellations(&[row], mode, 1, &mut cancellations);
+        assert_eq!(cancellations[0].ticks_left, 9);
+    }
+
+    #[test]
+    fn schedule_covers_the_horizon_and_at_least_a_page() {
+        let mode = BoardMode::new(BoardKind::Departures);
+        let names = vec!["YORK".to_string()];
+        let start = Local::now();
+        let trains = create_trains(mode, &names, start, &mut ChaChaRng::seed_from_u64(1));
+        let horizon = start.add(Duration::hours(SCHEDULE_HORIZON));
+        assert!(trains.len() >= mode.train_rows().len());
+        assert!(trains.iter().all(|train| train.time < horizon));
+        // Another train would not fit within the horizon
+        let last = trains.last().unwrap().time;
+        assert!(last.add(Duration::minutes(TIME_DIFFERENCE_MAXMIMUM)) >= horizon);
+        assert_eq!(
+            page_count(mode, &trains),
+            trains.len().div_ceil(mode.train_rows().len())
+        );
+    }
+
+    #[test]
+    fn split_flap_rolls_towards_the_target_on_its_own() {
+        let mut slots = board_slots();
+        let mut split_flap = SplitFlap::new();
+        slots[slot_index(0, 3)].set_target_payload(glyph('B'));
+        split_flap.tick(&slots);
+        assert!(!split_flap.rows_settled(&slots, &[3]));
+        assert!(split_flap.rows_settled(&slots, &[4]));
+
+        // Blank, A, then B
+        (0..2 * FLAP_STEP_DURATION).for_each(|_| split_flap.tick(&slots));
+        assert!(split_flap.rows_settled(&slots, &[3]));
+        assert!(split_flap.shown_glyph(slot_index(0, 3)) == glyph('B').map(|p| p.cargo));
+        // The swarm still has its own work to do
+        assert!(!rows_settled(&slots, &[3]));
+    }
+
+    #[test]
+    fn layout_origin_moves_the_board() {
+        let mut settings = LayoutSettings::default();
+        assert_eq!(
+            settings.slot_position(0, 0),
+            [f64::from(BOARD_LEFT_MARGIN), f64::from(BOARD_TOP_MARGIN)]
+        );
+        settings.apply_directive("origin 10 20");
+        assert_eq!(
+            settings.slot_position(1, 2),
+            [
+                10.0 + f64::from(TILE_WIDTH + TILE_SPACING),
+                20.0 + f64::from(2 * (TILE_HEIGHT + TILE_SPACING))
+            ]
+        );
+        settings.apply_directive("origin 10");
+        assert_eq!(settings.origin, Some([10.0, 20.0]));
+    }
+
+    #[test]
+    fn file_watcher_reports_new_and_modified_files() {
+        let dir = std::env::temp_dir().join(format!("timetable-watch-{}", std::process::id()));
+        let nested = dir.join("tiles");
+        std::fs::create_dir_all(&nested).unwrap();
+        let existing = dir.join("existing.txt");
+        std::fs::write(&existing, "a").unwrap();
+
+        let mut watcher = FileWatcher::new(&[dir.to_string_lossy().into_owned()]);
+        watcher.ticks_left = 1;
+        assert!(watcher.tick().is_empty());
+
+        let added = nested.join("added.png");
+        std::fs::write(&added, "b").unwrap();
+        watcher.ticks_left = 1;
+        assert_eq!(watcher.tick(), vec![added.to_string_lossy().into_owned()]);
+        watcher.ticks_left = 1;
+        assert!(watcher.tick().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn status_colours_follow_the_theme_tint() {
+        let mut theme = Theme::default();
+        theme.apply_setting("tint 0.5 0.5 0.5 0.8");
+        assert_eq!(TileColor::Default.rgba(&theme), [0.5, 0.5, 0.5, 0.8]);
+        assert_eq!(TileColor::Time.rgba(&theme), [0.5, 0.375, 0.1, 0.8]);
+
+        theme.apply_setting("color time 0.2 0.4 1.0 1.0");
+        assert_eq!(TileColor::Time.rgba(&theme), [0.1, 0.2, 0.5, 0.8]);
+        assert_eq!(TileColor::Clock.rgba(&theme), [0.5, 0.375, 0.1, 0.8]);
+    }
+
+    #[test]
+    fn board_events_survive_a_round_trip() {
+        [
+            BoardEvent::Departure,
+            BoardEvent::Cancellation,
+            BoardEvent::ToggleCarriers,
+            BoardEvent::ToggleDisplay,
+            BoardEvent::AddCarrier,
+            BoardEvent::RetireCarrier,
+            BoardEvent::Removal(7),
+            BoardEvent::Page(2),
+        ]
+        .iter()
+        .for_each(|event| assert_eq!(BoardEvent::parse(&event.to_string()), Some(*event)));
+    }
+
+    #[test]
+    fn malformed_board_events_are_rejected() {
+        assert_eq!(BoardEvent::parse(""), None);
+        assert_eq!(BoardEvent::parse("arrival"), None);
+        assert_eq!(BoardEvent::parse("removal"), None);
+        assert_eq!(BoardEvent::parse("removal x"), None);
+        assert_eq!(BoardEvent::parse("removal 1 2"), None);
+        assert_eq!(BoardEvent::parse("page"), None);
+        assert_eq!(BoardEvent::parse("departure now"), None);
+    }
+
+    #[test]
+    fn replayed_events_are_taken_at_their_ticks_in_order() {
+        let mut log = EventLog {
+            recording: None,
+            replay: Some(VecDeque::from(vec![
+                (3, BoardEvent::Departure),
+                (3, BoardEvent::AddCarrier),
+                (5, BoardEvent::Removal(1)),
+                (5, BoardEvent::Page(1)),
+                (8, BoardEvent::Cancellation),
+            ])),
+            seed: None,
+            start_time: None,
+            carrier_types: None,
+            simulated_clock: true,
+        };
+        assert!(log.take_inputs(2).is_empty());
+        assert_eq!(
+            log.take_inputs(3),
+            vec![BoardEvent::Departure, BoardEvent::AddCarrier]
+        );
+        assert_eq!(log.take_removal(4), None);
+
+        // Inputs of later ticks wait behind the changes the board makes itself
+        assert!(log.take_inputs(8).is_empty());
+        assert_eq!(log.take_page(5), None);
+        assert_eq!(log.take_removal(5), Some(1));
+        assert_eq!(log.take_page(5), Some(1));
+        assert!(log.replaying());
+        assert_eq!(log.take_inputs(8), vec![BoardEvent::Cancellation]);
+        assert!(!log.replaying());
+    }
+
+    #[test]
+    fn simulation_steps_follow_the_time_scale() {
+        let mut simulation = Simulation::new();
+        assert_eq!(simulation.steps(), 1);
+
+        simulation.scale_time(2.0);
+        assert_eq!(simulation.steps(), 2);
+
+        simulation.scale_time(0.25);
+        let steps: Vec<u32> = (0..4).map(|_| simulation.steps()).collect();
+        assert_eq!(steps, vec![0, 1, 0, 1]);
+
+        simulation.scale_time(1000.0);
+        assert_eq!(simulation.steps(), TIME_SCALE_MAX as u32);
+    }
+
+    #[test]
+    fn paused_simulation_only_runs_requested_steps() {
+        let mut simulation = Simulation::new();
+        simulation.toggle_pause();
+        assert_eq!(simulation.steps(), 0);
+        simulation.step();
+        assert_eq!(simulation.steps(), 1);
+        assert_eq!(simulation.steps(), 0);
+
+        simulation.toggle_pause();
+        simulation.step();
+        assert_eq!(simulation.steps(), 1);
+    }
+
+    // Board of two slots, a letter waiting to be moved from the first to the second one
+    fn swarm_with_work() -> MyGameType {
+        let mut game = swarm_it::Swarm::<Glyph>::new();
+        game.add_slot(Slot::new(100.0, 100.0, glyph('A'), None, SlotKind::CLASSIC));
+        game.add_slot(Slot::new(300.0, 100.0, None, glyph('A'), SlotKind::CLASSIC));
+        game.slot_data_changed();
+        game
+    }
+
+    fn add_carrier(game: &mut MyGameType, states: &mut CarrierStates, x: f64, y: f64) {
+        game.add_carrier(Carrier::new(x, y));
+        states.add(0, [x, y]);
+    }
+
+    fn settles_within(game: &mut MyGameType, ticks: u32) -> bool {
+        (0..ticks).any(|_| game.tick())
+    }
+
+    #[test]
+    fn carrier_states_follow_the_swarm_carriers() {
+        let mut game = swarm_with_work();
+        let mut states = CarrierStates::new(ChaChaRng::seed_from_u64(0));
+        add_carrier(&mut game, &mut states, 100.0, 300.0);
+        game.tick();
+        states.tick(game.get_carriers(), 0.1);
+        // Carrier turns towards its target before it moves
+        assert_eq!(states.states[0].swarm_state, swarm_it::State::TARGETING(0));
+        assert_eq!(states.states[0].speed(), 0.0);
+        assert_eq!(states.states[0].trail.len(), 1);
+
+        let moved = (0..100).any(|_| {
+            game.tick();
+            states.tick(game.get_carriers(), 0.1);
+            states.states[0].speed() > 0.0
+        });
+        assert!(moved);
+        let position = game.get_carriers()[0].get_position();
+        assert_eq!(states.states[0].position, [position.x, position.y]);
+
+        assert!(states.remove(1).is_none());
+        assert_eq!(states.remove(0).map(|state| state.kind), Some(0));
+        assert!(states.states.is_empty());
+    }
+
+    #[test]
+    fn auto_swarm_shrinks_without_holding_the_board_back() {
+        let mut auto_swarm = AutoSwarm {
+            min: 1,
+            max: 2,
+            spawn_interval: AUTO_SWARM_SPAWN_INTERVAL,
+            retire_interval: AUTO_SWARM_RETIRE_INTERVAL,
+            ticks_left: 0,
+        };
+        assert_eq!(auto_swarm.tick(100, 1, 1), 1);
+        assert_eq!(auto_swarm.ticks_left, AUTO_SWARM_SPAWN_INTERVAL);
+        auto_swarm.ticks_left = 0;
+
+        let mut game = swarm_with_work();
+        let mut states = CarrierStates::new(ChaChaRng::seed_from_u64(0));
+        add_carrier(&mut game, &mut states, 100.0, 300.0);
+        add_carrier(&mut game, &mut states, 500.0, 500.0);
+        game.tick();
+        // Nothing to shrink while every carrier is busy
+        assert_eq!(auto_swarm.tick(0, 2, 0), 0);
+        assert_eq!(auto_swarm.ticks_left, 0);
+
+        let idle_carriers = game
+            .get_carriers()
+            .iter()
+            .filter(|c| c.get_state() == swarm_it::State::IDLE)
+            .count();
+        assert_eq!(idle_carriers, 1);
+        assert_eq!(auto_swarm.tick(0, 2, idle_carriers), -1);
+        assert_eq!(auto_swarm.ticks_left, AUTO_SWARM_RETIRE_INTERVAL);
+        let types = [CarrierType::default()];
+        assert!(retire_carrier(&mut game, &mut states, &types, &[[0.0, 0.0]]).is_some());
+
+        assert!(settles_within(&mut game, 5000));
+        assert!(game.get_slots().iter().all(|slot| !slot.is_taken_care_of()));
+    }
+
+    #[test]
+    fn exit_positions() {
+        let width = f64::from(SCREEN_SIZE_NATIVE[0]);
+        let height = f64::from(SCREEN_SIZE_NATIVE[1]);
+        assert_eq!(exit_position("left"), Some([-EXIT_MARGIN, height / 2.0]));
+        assert_eq!(
+            exit_position("right 100"),
+            Some([width + EXIT_MARGIN, 100.0])
+        );
+        assert_eq!(exit_position("top 400"), Some([400.0, -EXIT_MARGIN]));
+        assert_eq!(
+            exit_position("bottom"),
+            Some([width / 2.0, height + EXIT_MARGIN])
+        );
+        assert_eq!(exit_position("-10 20.5"), Some([-10.0, 20.5]));
+        assert_eq!(exit_position(""), None);
+        assert_eq!(exit_position("100"), None);
+        assert_eq!(exit_position("top far"), None);
+        assert_eq!(exit_position("left 1 2"), None);
+    }
+
+    #[test]
+    fn reloaded_exits_wait_for_the_busy_carriers() {
+        let mut game = swarm_with_work();
+        add_pits_and_spawners(&mut game, &LayoutSettings::default());
+        let mut states = CarrierStates::new(ChaChaRng::seed_from_u64(0));
+        add_carrier(&mut game, &mut states, 100.0, 300.0);
+        game.tick();
+
+        let mut settings = LayoutSettings::default();
+        settings.apply_directive("pit top 400");
+        settings.apply_directive("spawner bottom");
+        let mut pending = Some(Layout {
+            settings,
+            cells: Vec::new(),
+        });
+        assert!(apply_pending_layout(&mut game, &mut pending, 2).is_none());
+        assert!(pending.is_some());
+        assert_eq!(game.get_slots().len(), 6);
+
+        assert!(settles_within(&mut game, 5000));
+        let applied = apply_pending_layout(&mut game, &mut pending, 2).unwrap();
+        assert!(pending.is_none());
+        assert_eq!(applied.pits(), vec![[400.0, -EXIT_MARGIN]]);
+        let slots = game.get_slots();
+        assert_eq!(slots.len(), 4);
+        assert!(slots[2].is_pit() && slots[3].is_spawner());
+        assert_eq!(slots[2].get_position().x, 400.0);
+        assert!(slots[1].get_payloads()[0] == glyph('A'));
+    }
+
+    #[test]
+    fn only_carriers_idle_in_the_swarm_are_retired() {
+        let mut game = swarm_with_work();
+        let mut states = CarrierStates::new(ChaChaRng::seed_from_u64(0));
+        add_carrier(&mut game, &mut states, 100.0, 300.0);
+        game.tick();
+        add_carrier(&mut game, &mut states, 500.0, 500.0);
+        states.tick(game.get_carriers(), 0.1);
+        let types = [CarrierType::default()];
+        let pits = [[0.0, 0.0]];
+
+        // The first carrier stands still while turning, but it already holds its target
+        let retired = retire_carrier(&mut game, &mut states, &types, &pits).unwrap();
+        assert_eq!(retired.position, [500.0, 500.0]);
+        assert_eq!(game.get_carriers().len(), 1);
+        assert_eq!(states.states.len(), 1);
+        assert!(retire_carrier(&mut game, &mut states, &types, &pits).is_none());
+
+        assert!(settles_within(&mut game, 5000));
+        assert!(game.get_slots()[1].get_payloads()[0] == glyph('A'));
+    }
+
+    #[test]
+    fn carrier_types_take_an_optional_tint() {
+        let plain = CarrierType::parse("pigeon 0.16 24.0 2 1.0 carrier").unwrap();
+        assert_eq!(plain.tint, [1.0; 4]);
+        let tinted = CarrierType::parse("goose 0.08 14.0 1 1.4 carrier/ 1.0 0.5 0.25 1.0").unwrap();
+        assert_eq!(tinted.tint, [1.0, 0.5, 0.25, 1.0]);
+        assert_eq!(tinted.sprites.as_deref(), Some("carrier"));
+        assert!(CarrierType::parse("goose 0.08 14.0 1 1.4 carrier 1.0 0.5").is_none());
+    }
+
+    #[test]
+    fn carrier_types_are_recorded_as_their_definitions() {
+        let line = "goose 0.08 14 1 1.4 carrier 1 0.5 0.25 1";
+        let carrier_type = CarrierType::parse(line).unwrap();
+        assert_eq!(carrier_type.definition().as_deref(), Some(line));
+        let plain = CarrierType::parse("pigeon 0.16 24.5 2 1 carrier").unwrap();
+        assert_eq!(
+            plain.definition().as_deref(),
+            Some("pigeon 0.16 24.5 2 1 carrier")
+        );
+        assert!(CarrierType::default().definition().is_none());
+
+        let types = parse_carrier_types(vec![line.to_string()]);
+        assert_eq!(types.len(), 1);
+        assert_eq!(types[0].max_speed, 14.0);
+        assert_eq!(parse_carrier_types(Vec::new())[0].name, "default");
+    }
+
+    #[test]
+    fn metrics_transitions_are_started_by_their_causes() {
+        let mut metrics = SwarmMetrics {
+            causes: Vec::new(),
+            current: None,
+            completed: Vec::new(),
+            stream: None,
+        };
+        // Clock ticking alone does not make a transition
+        metrics.tick(&[], false);
+        assert!(metrics.current.is_none());
+
+        // Carrier turning towards its target is busy, even though it does not move
+        let mut states = CarrierStates::new(ChaChaRng::seed_from_u64(0));
+        states.add(0, [0.0, 0.0]);
+        states.add(0, [0.0, 0.0]);
+        states.states[0].swarm_state = swarm_it::State::TARGETING(0);
+        metrics.cause(TransitionCause::Page);
+        metrics.tick(&states.states, true);
+        assert_eq!(metrics.completed[0].carrier_ticks, 2);
+        assert_eq!(metrics.completed[0].idle_carrier_ticks, 1);
+        metrics.completed.clear();
+
+        metrics.cause(TransitionCause::Departure);
+        metrics.tick(&[], false);
+        metrics.cause(TransitionCause::Scroll);
+        metrics.cause(TransitionCause::Departure);
+        metrics.tick(&[], true);
+        assert_eq!(metrics.completed.len(), 1);
+        assert_eq!(
+            metrics.completed[0].causes,
+            vec![TransitionCause::Departure, TransitionCause::Scroll]
+        );
+        assert_eq!(metrics.completed[0].ticks, 2);
+
+        metrics.cause(TransitionCause::Page);
+        metrics.tick(&[], false);
+        metrics.finish();
+        assert_eq!(metrics.completed.len(), 2);
+        assert!(metrics.completed[1].interrupted);
+        assert_eq!(
+            metrics.completed[1].to_csv(1),
+            "1,page,1,0.0,0,0,0.000,true"
+        );
+    }
+
+    #[test]
+    fn start_time_is_given_or_derived_from_the_seed() {
+        let start = parse_start_time("07:30").unwrap();
+        assert_eq!((start.hour(), start.minute(), start.second()), (7, 30, 0));
+        let start = parse_start_time("2020-05-01T23:15:42+00:00").unwrap();
+        assert_eq!(start.timestamp(), 1_588_374_942);
+        assert!(parse_start_time("7 o'clock").is_none());
+
+        let start = seeded_start_time(SECONDS_PER_DAY + 3661).unwrap();
+        assert_eq!((start.hour(), start.minute(), start.second()), (1, 1, 1));
+    }
+}